@@ -0,0 +1,149 @@
+use clap::Subcommand;
+use derive_more::Display;
+use error_stack::{Result, ResultExt};
+use memobot::bot::commands::{self, CommandScope, SyncSummary};
+use memobot_kernel::Kernel;
+use twilight_model::id::{marker::GuildMarker, Id};
+
+use crate::{command_registry, init, load_extensions, Setup};
+
+#[derive(Debug, Display)]
+#[display(fmt = "Could not manage application commands")]
+pub struct ManageCommandsError;
+impl error_stack::Context for ManageCommandsError {}
+
+#[derive(Debug, Subcommand)]
+pub enum CommandsCommand {
+    /// Lists registered commands and whether Discord has them.
+    List,
+    /// Registers commands on Discord, only changing the ones that differ.
+    Register {
+        /// Registers global commands to this guild instead.
+        #[arg(long)]
+        guild: Option<Id<GuildMarker>>,
+    },
+    /// Deletes every global command from Discord, even when
+    /// `MEMOBOT_DEV_GUILD_ID` is set.
+    Purge {
+        /// Deletes the commands of this guild instead of global ones.
+        #[arg(long)]
+        guild: Option<Id<GuildMarker>>,
+    },
+}
+
+pub fn run(command: CommandsCommand) -> Result<(), ManageCommandsError> {
+    let Setup { kernel, rt, .. } = init().change_context(ManageCommandsError)?;
+    let global_scope =
+        || commands::global_scope(&kernel.config()).change_context(ManageCommandsError);
+
+    rt.block_on(async {
+        match command {
+            CommandsCommand::List => list(&kernel, global_scope()?).await,
+            CommandsCommand::Register { guild } => {
+                let scope = match guild {
+                    Some(guild_id) => CommandScope::Guild(guild_id),
+                    None => global_scope()?,
+                };
+                register(&kernel, scope).await
+            }
+            CommandsCommand::Purge { guild } => {
+                let scope = guild.map_or(CommandScope::Global, CommandScope::Guild);
+                purge(&kernel, scope).await
+            }
+        }
+    })
+}
+
+async fn list(kernel: &Kernel, global_scope: CommandScope) -> Result<(), ManageCommandsError> {
+    let extensions = load_extensions(kernel).change_context(ManageCommandsError)?;
+    let registry = command_registry(&extensions);
+
+    let mut scopes = vec![global_scope];
+    for scope in registry.scopes() {
+        if scope != CommandScope::Global && !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+
+    for scope in scopes {
+        let mut local = registry
+            .definitions_with_scope(scope)
+            .map(|v| v.name.clone())
+            .collect::<Vec<_>>();
+
+        if scope == global_scope {
+            local.extend(
+                registry
+                    .definitions_with_scope(CommandScope::Global)
+                    .map(|v| v.name.clone()),
+            );
+        }
+        local.sort();
+        local.dedup();
+
+        let remote = commands::fetch(kernel, scope)
+            .await
+            .change_context(ManageCommandsError)?
+            .into_iter()
+            .map(|v| v.name)
+            .collect::<Vec<_>>();
+
+        println!("{scope} commands:");
+        for name in &local {
+            let status = if remote.contains(name) {
+                "registered"
+            } else {
+                "not registered on Discord"
+            };
+            println!("  /{name} ({status})");
+        }
+        for name in remote.iter().filter(|v| !local.contains(v)) {
+            println!("  /{name} (only on Discord)");
+        }
+    }
+
+    Ok(())
+}
+
+async fn register(kernel: &Kernel, global_scope: CommandScope) -> Result<(), ManageCommandsError> {
+    let extensions = load_extensions(kernel).change_context(ManageCommandsError)?;
+    let registry = command_registry(&extensions);
+
+    let summaries = commands::sync_to(kernel, &registry, global_scope)
+        .await
+        .change_context(ManageCommandsError)?;
+
+    for (scope, summary) in summaries {
+        print_summary(scope, &summary);
+    }
+
+    Ok(())
+}
+
+async fn purge(kernel: &Kernel, scope: CommandScope) -> Result<(), ManageCommandsError> {
+    println!("Deleting {scope} commands...");
+    let deleted = commands::purge(kernel, scope)
+        .await
+        .change_context(ManageCommandsError)?;
+
+    println!("Deleted {} {scope} command(s)", deleted.len());
+    for name in deleted {
+        println!("  /{name}");
+    }
+
+    Ok(())
+}
+
+fn print_summary(scope: CommandScope, summary: &SyncSummary) {
+    println!("{scope} commands:");
+    for (names, action) in [
+        (&summary.created, "created"),
+        (&summary.updated, "updated"),
+        (&summary.deleted, "deleted"),
+    ] {
+        for name in names {
+            println!("  /{name} ({action})");
+        }
+    }
+    println!("  {} unchanged", summary.unchanged);
+}
@@ -0,0 +1,115 @@
+use derive_more::Display;
+use error_stack::{Context, Report, Result};
+use memobot_env_vars::{EnvVar, Source};
+
+#[derive(Debug, Display)]
+#[display(fmt = "Configuration is invalid")]
+pub struct ConfigCheckError;
+impl error_stack::Context for ConfigCheckError {}
+
+/// Loads every config, prints the effective configuration and
+/// reports all problems found.
+pub fn check() -> Result<(), ConfigCheckError> {
+    let mut problems = Vec::new();
+
+    match memobot_env_vars::load_file() {
+        Ok(Some(path)) => println!("Configuration file: {}", path.display()),
+        Ok(None) => println!("Configuration file: none"),
+        Err(error) => {
+            println!("Configuration file: failed to load");
+            push_problems(&mut problems, vec![error]);
+        }
+    }
+
+    for (name, vars) in memobot::config::sections() {
+        print_section(name, &vars);
+    }
+
+    push_problems(&mut problems, memobot_kernel::Config::check_env());
+    push_problems(
+        &mut problems,
+        memobot_kernel::config::SentryConfig::check_env(),
+    );
+    push_problems(&mut problems, memobot_paradise::Config::check_env());
+    push_problems(&mut problems, memobot::util::tracing::check_env());
+
+    let known = memobot::config::sections()
+        .into_iter()
+        .flat_map(|(_, vars)| vars)
+        .collect::<Vec<_>>();
+
+    let unknown = memobot_env_vars::unknown_file_keys(&known);
+    if !unknown.is_empty() {
+        println!("\n[unknown]");
+        for origin in unknown {
+            println!("  {origin} is not read by any config");
+        }
+    }
+
+    let total = problems.len();
+    let mut problems = problems.into_iter();
+    let Some(mut report) = problems.next() else {
+        println!("\nNo problems found.");
+        return Ok(());
+    };
+
+    for problem in problems {
+        report.extend_one(problem);
+    }
+
+    Err(report.attach_printable(format!("found {total} problem(s)")))
+}
+
+fn push_problems<C: Context>(
+    problems: &mut Vec<Report<ConfigCheckError>>,
+    reports: Vec<Report<C>>,
+) {
+    problems.extend(
+        reports
+            .into_iter()
+            .map(|v| v.change_context(ConfigCheckError)),
+    );
+}
+
+fn print_section(name: &str, vars: &[EnvVar]) {
+    println!("\n[{name}]");
+
+    let disabled_by = vars
+        .iter()
+        .filter_map(|v| v.enabled_by)
+        .find(|key| !matches!(memobot_env_vars::var(key), Ok(Some(..))));
+
+    if let Some(key) = disabled_by {
+        println!("  disabled, set {key} to enable it");
+        return;
+    }
+
+    for var in vars {
+        println!("  {} = {}", var.key, describe_value(var));
+    }
+}
+
+fn describe_value(var: &EnvVar) -> String {
+    let key = memobot_env_vars::resolve_key(var.key, var.aliases);
+    let (value, source) = match memobot_env_vars::var_with_source(key) {
+        Ok(Some(v)) => v,
+        Ok(None) => {
+            return match var.default_value {
+                Some(default) => format!("{} (default)", default()),
+                None => "not set".into(),
+            };
+        }
+        Err(..) => return "<unreadable>".into(),
+    };
+
+    let value = if var.sensitive {
+        "<redacted>".into()
+    } else {
+        format!("{value:?}")
+    };
+
+    match source {
+        Source::Env if key != var.key => format!("{value} (environment as {key})"),
+        source => format!("{value} ({source})"),
+    }
+}
@@ -0,0 +1,57 @@
+use derive_more::Display;
+use error_stack::{Report, Result, ResultExt};
+use memobot_kernel::config::ApiConfig;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream};
+use std::time::Duration;
+
+#[derive(Debug, Display)]
+#[display(fmt = "memobot is not healthy")]
+pub struct HealthCheckError;
+impl error_stack::Context for HealthCheckError {}
+
+const TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Requests `/healthz` from the API server listening at the address
+/// and port of the resolved configuration, including the values
+/// set in the configuration file.
+pub fn check() -> Result<(), HealthCheckError> {
+    memobot_env_vars::load_file().change_context(HealthCheckError)?;
+    let config = ApiConfig::from_env().change_context(HealthCheckError)?;
+
+    // The API server listens on every interface by default
+    let ip = match config.address() {
+        IpAddr::V4(ip) if ip.is_unspecified() => IpAddr::V4(Ipv4Addr::LOCALHOST),
+        IpAddr::V6(ip) if ip.is_unspecified() => IpAddr::V6(Ipv6Addr::LOCALHOST),
+        ip => ip,
+    };
+    let addr = SocketAddr::new(ip, config.port());
+
+    let status = request_status(addr, "/healthz")
+        .change_context(HealthCheckError)
+        .attach_printable_lazy(|| format!("could not reach the API server at {addr}"))?;
+
+    if status.split_whitespace().nth(1) != Some("200") {
+        return Err(Report::new(HealthCheckError))
+            .attach_printable(format!("unexpected response: {:?}", status.trim()));
+    }
+
+    println!("memobot is healthy");
+    Ok(())
+}
+
+/// Sends a `GET` request and returns the status line of the response.
+fn request_status(addr: SocketAddr, path: &str) -> std::io::Result<String> {
+    let mut stream = TcpStream::connect_timeout(&addr, TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+
+    write!(
+        stream,
+        "GET {path} HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n\r\n"
+    )?;
+
+    let mut status = String::new();
+    BufReader::new(stream).read_line(&mut status)?;
+    Ok(status)
+}
@@ -1,37 +1,213 @@
+use clap::{Parser, Subcommand};
 use derive_more::Display;
-use error_stack::{Result, ResultExt};
-use memobot_kernel::{Kernel, ShutdownReason};
+use error_stack::{FutureExt, Result, ResultExt};
+use futures::{FutureExt as _, TryFutureExt};
+use memobot::bot::commands::CommandRegistry;
+use memobot::bot::ShardStates;
+use memobot::util::tracing::LogFilter;
+use memobot_kernel::{ExtensionRegistry, Kernel, ShutdownPhase, ShutdownReason};
+use std::collections::HashMap;
+use std::future::IntoFuture;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::task::JoinSet;
 
-#[tracing::instrument(skip(kernel))]
-async fn init_shards(kernel: &Kernel) -> Result<Vec<twilight_gateway::Shard>, StartError> {
-    use twilight_gateway::stream::create_recommended;
-    use twilight_gateway::Intents;
+mod commands;
+mod config;
+mod health;
+mod paradise;
 
-    let intents = Intents::GUILDS | Intents::GUILD_INTEGRATIONS;
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
 
-    let primary_config = twilight_gateway::Config::new(kernel.config().token().into(), intents);
-    let shards = create_recommended(kernel.http(), primary_config, |_, builder| builder.build())
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Runs the bot (default).
+    Run,
+    /// Inspects the configuration.
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Manages application commands registered on Discord.
+    #[command(subcommand)]
+    Commands(commands::CommandsCommand),
+    /// Checks if the running bot's API server is healthy, using
+    /// the port from the environment or the configuration file.
+    Health,
+    /// Sends a test alert to Paradise.
+    SendTestAlert {
+        /// Sends the offline alert instead of the online one.
+        #[arg(long)]
+        offline: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigCommand {
+    /// Loads every config and reports all problems found.
+    Check,
+}
+
+#[tracing::instrument(skip_all)]
+async fn init_shards(
+    kernel: &Kernel,
+    extensions: &ExtensionRegistry,
+    states: &ShardStates,
+) -> Result<Vec<twilight_gateway::Shard>, StartError> {
+    use memobot_kernel::intents;
+    use twilight_gateway::stream::create_range;
+
+    let intents = intents::resolve(&kernel.config(), extensions, kernel.events())
+        .change_context(StartError)?;
+
+    tracing::info!("Using gateway intents: {}", intents::describe(intents));
+
+    // Shard count is not known until Discord tells us, sessions saved
+    // with a different shard count are discarded by `take_all`.
+    let total = kernel
+        .http()
+        .gateway()
+        .authed()
+        .into_future()
+        .change_context(StartError)
+        .and_then(|v| v.model().change_context(StartError))
         .await
-        .change_context(StartError)?
-        .collect();
+        .attach_printable("failed to get recommended shard count")?
+        .shards;
+
+    let sessions = match memobot::bot::session::take_all(kernel, total).await {
+        Ok(sessions) => sessions,
+        Err(error) => {
+            tracing::warn!(?error, "Failed to load saved gateway sessions");
+            HashMap::new()
+        }
+    };
+
+    let primary_config = twilight_gateway::Config::new(kernel.config().token().into(), intents);
+    let shards = create_range(0..total, total, primary_config, |id, builder| {
+        match sessions.get(&id.number()) {
+            Some(saved) => {
+                tracing::info!(shard.id = %id, "Resuming saved gateway session");
+                // Kept for the next shutdown since resumed
+                // sessions don't receive a Ready event
+                states.set_resume_url(id, Some(saved.resume_url.clone()));
+
+                // Shards connect to the proxy URL until they get a
+                // Ready event, so this is where the session resumes at
+                builder
+                    .session(saved.session.clone())
+                    .proxy_url(saved.resume_url.clone())
+                    .build()
+            }
+            None => builder.build(),
+        }
+    })
+    .collect();
 
     Ok(shards)
 }
 
+/// Loads every in-house extension. Extensions that are not
+/// configured are skipped.
+fn load_extensions(kernel: &Kernel) -> Result<ExtensionRegistry, StartError> {
+    let mut extensions = ExtensionRegistry::new();
+    extensions
+        .load::<memobot_paradise::Service>(kernel)
+        .change_context(StartError)?;
+
+    Ok(extensions)
+}
+
 #[derive(Debug, Display)]
 #[display(fmt = "Failed to start memobot service")]
 struct StartError;
 impl error_stack::Context for StartError {}
 
-fn main() -> Result<(), StartError> {
-    memobot::util::tracing::init();
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match cli.command.unwrap_or(Command::Run) {
+        Command::Run => match run() {
+            Ok(reason) => ExitCode::from(reason.exit_code()),
+            Err(error) => exit(Err(error)),
+        },
+        Command::Config(ConfigCommand::Check) => exit(config::check()),
+        Command::Commands(command) => exit(commands::run(command)),
+        Command::Health => exit(health::check()),
+        Command::SendTestAlert { offline } => exit(paradise::send_test_alert(!offline)),
+    }
+}
+
+fn exit<C: error_stack::Context>(result: Result<(), C>) -> ExitCode {
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("Error: {error:?}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Exit code used when the bot did not shut down within
+/// `MEMOBOT_SHUTDOWN_TIMEOUT` and had to be stopped, other exit
+/// codes are listed in [`ShutdownReason::exit_code`].
+const FORCED_SHUTDOWN_EXIT_CODE: i32 = 3;
+
+/// Logs what is still running and exits the process right away,
+/// which aborts every remaining task.
+fn force_exit(kernel: &Kernel, services: &[&str], timeout: Duration) -> ! {
+    tracing::error!(
+        ?services,
+        reason = ?kernel.shutdown_reason(),
+        "Shutdown did not finish within {timeout:?}, forcing exit"
+    );
+
+    for task in kernel.background_tasks() {
+        tracing::error!(
+            task.id = %task.id,
+            task.blocking = %task.blocking,
+            "Background task spawned at {} is still running after {:?}",
+            task.location,
+            task.elapsed()
+        );
+    }
+
+    // The Sentry guard is not dropped on exit
+    if let Some(client) = sentry::Hub::current().client() {
+        client.flush(Some(Duration::from_secs(2)));
+    }
+
+    std::process::exit(FORCED_SHUTDOWN_EXIT_CODE)
+}
+
+/// Everything the subcommands share, set up in the same way as
+/// running the bot but without connecting to the gateway.
+struct Setup {
+    kernel: Kernel,
+    log_filter: LogFilter,
+    rt: tokio::runtime::Runtime,
+    _sentry: Option<sentry::ClientInitGuard>,
+}
+
+fn init() -> Result<Setup, StartError> {
+    let config_file = memobot_env_vars::load_file()
+        .change_context(StartError)
+        .attach_printable("failed to load configuration file")?;
+
+    let log_filter = memobot::util::tracing::init().change_context(StartError)?;
+    if let Some(path) = config_file {
+        tracing::info!("Using configuration file at {}", path.display());
+        memobot::config::warn_unknown_keys();
+    }
 
     let config = memobot_kernel::Config::from_env()
         .change_context(StartError)
         .attach_printable("failed to load configuration")?;
 
-    let _sentry = memobot::sentry::init();
+    let sentry = memobot::sentry::init(config.environment());
     tracing::info!("Running memobot with {} thread(s)", config.workers());
     tracing::trace!("Initializing tokio runtime");
 
@@ -46,25 +222,60 @@ fn main() -> Result<(), StartError> {
         .block_on(memobot_kernel::Kernel::init(config))
         .change_context(StartError)?;
 
-    let paradise = memobot_paradise::Config::from_env()
-        .change_context(StartError)
-        .attach_printable("failed to load Paradise configuration")?
-        .map(|v| memobot_paradise::Service::new(v, kernel.clone()));
+    Ok(Setup {
+        kernel,
+        log_filter,
+        rt,
+        _sentry: sentry,
+    })
+}
+
+/// Creates a [`CommandRegistry`] with the built-in commands and
+/// the commands of every loaded extension.
+fn command_registry(extensions: &ExtensionRegistry) -> CommandRegistry {
+    let mut commands = memobot::bot::commands::registry();
+    extensions.register_commands(&mut commands);
+    commands
+}
+
+fn run() -> Result<ShutdownReason, StartError> {
+    let Setup {
+        kernel,
+        log_filter,
+        rt,
+        _sentry,
+    } = init()?;
+
+    let extensions = Arc::new(load_extensions(&kernel)?);
+    let commands = Arc::new(command_registry(&extensions));
 
     rt.block_on(async move {
         use actix_web::{web, App, HttpServer};
 
         let mut services = JoinSet::new();
         let kernel_1 = kernel.clone();
-        let api_config = kernel.config().api();
+        let commands_1 = commands.clone();
+        let log_filter_1 = log_filter.clone();
+        let extensions_1 = extensions.clone();
+        let api_config = kernel.config().api().clone();
+
+        let states = ShardStates::new();
+        let states_1 = states.clone();
 
         let http = HttpServer::new(move || {
             App::new()
-                .app_data(web::Data::new(paradise.clone()))
                 .app_data(web::Data::new(kernel_1.clone()))
-                .service(web::scope("/paradise").configure(memobot_paradise::api::configure))
+                .app_data(web::Data::new(states_1.clone()))
+                .app_data(web::Data::from(commands_1.clone()))
+                .app_data(web::Data::new(log_filter_1.clone()))
+                .configure(memobot::api::admin::configure)
+                .configure(memobot::api::health::configure)
+                .configure(memobot::api::metrics::configure)
+                .configure(|cfg| extensions_1.configure_api(cfg))
         })
         .workers(1)
+        // Stopped by the shutdown phases instead
+        .disable_signals()
         .bind((api_config.address(), api_config.port()))
         .change_context(StartError)
         .attach_printable("failed to initialize API server")?
@@ -76,35 +287,76 @@ fn main() -> Result<(), StartError> {
             api_config.port()
         );
 
+        let server_handle = http.handle();
+        kernel.on_shutdown(ShutdownPhase::StopHttp, |_| async move {
+            tracing::info!("Shutting down API server...");
+            server_handle.stop(true).await;
+        });
+
         let kernel_1 = kernel.clone();
         services.spawn(async move {
-            let server_handle = http.handle();
-            tokio::select! {
-                _ = kernel_1.shutdown_guard() => {
-                    tracing::info!("Shutting down API server...");
-                    server_handle.stop(true).await;
-                },
-                Err(error) = http => {
-                    tracing::error!(?error, "Critical error found in HTTP API server");
-                    kernel_1.shutdown(ShutdownReason::ApiServerFailed);
-                }
+            if let Err(error) = http.await {
+                tracing::error!(?error, "Critical error found in HTTP API server");
+                kernel_1.shutdown(ShutdownReason::ApiServerFailed);
             }
+            "API server"
         });
 
-        let shards = init_shards(&kernel).await?;
-        services.spawn(memobot::services::bot::start(kernel.clone(), shards));
+        extensions.start(&kernel);
+        services.spawn(
+            memobot::services::reload::start(kernel.clone(), extensions.clone(), log_filter)
+                .map(|()| "config reload"),
+        );
+
+        let shards = init_shards(&kernel, &extensions, &states).await?;
+        services.spawn(
+            memobot::services::bot::start(kernel.clone(), commands, extensions, states, shards)
+                .map(|()| "bot"),
+        );
 
         let shutdown_signal = memobot::util::shutdown_signal();
-        tokio::select! {
-            _ = kernel.shutdown_guard() => {},
-            _ = shutdown_signal => {
-                kernel.shutdown(ShutdownReason::Signal);
-            },
+        tokio::pin!(shutdown_signal);
+
+        let mut running = vec!["API server", "config reload", "bot"];
+        loop {
+            tokio::select! {
+                _ = kernel.shutdown_guard() => break,
+                _ = &mut shutdown_signal => {
+                    kernel.shutdown(ShutdownReason::Signal);
+                    break;
+                },
+                Some(result) = services.join_next() => match result {
+                    Ok(name) => running.retain(|v| *v != name),
+                    Err(error) => {
+                        tracing::error!(?error, "Service has stopped unexpectedly");
+                        kernel.shutdown(ShutdownReason::Panic);
+                    }
+                },
+            };
+        }
+
+        let timeout = kernel.config().shutdown_timeout();
+        let graceful = async {
+            kernel.run_shutdown_phases().await;
+            while let Some(result) = services.join_next().await {
+                if let Ok(name) = result {
+                    running.retain(|v| *v != name);
+                }
+            }
         };
 
-        while services.join_next().await.is_some() {}
+        if tokio::time::timeout(timeout, graceful).await.is_err() {
+            force_exit(&kernel, &running, timeout);
+        }
 
-        tracing::info!("All services has been gracefully shutdown. Closing application...");
-        Ok(())
+        let reason = kernel
+            .shutdown_reason()
+            .expect("kernel should be shutting down");
+
+        tracing::info!(
+            exit_code = %reason.exit_code(),
+            "All services has been gracefully shutdown ({reason}). Closing application..."
+        );
+        Ok(reason)
     })
 }
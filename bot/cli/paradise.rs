@@ -0,0 +1,33 @@
+use derive_more::Display;
+use error_stack::{Report, Result, ResultExt};
+use memobot_kernel::Extension;
+use memobot_paradise::bot::sanctuary::alert_everyone;
+
+use crate::{init, Setup};
+
+#[derive(Debug, Display)]
+#[display(fmt = "Could not send test alert to Paradise")]
+pub struct SendTestAlertError;
+impl error_stack::Context for SendTestAlertError {}
+
+/// Sends the same alert Paradise sends when the sanctuary
+/// goes online or offline.
+pub fn send_test_alert(is_online: bool) -> Result<(), SendTestAlertError> {
+    let Setup { kernel, rt, .. } = init().change_context(SendTestAlertError)?;
+
+    let Some(service) =
+        memobot_paradise::Service::load(&kernel).change_context(SendTestAlertError)?
+    else {
+        return Err(Report::new(SendTestAlertError))
+            .attach_printable("Paradise is disabled, set MEMOBOT_PARADISE_GUILD_ID to enable it");
+    };
+
+    rt.block_on(alert_everyone(&service, is_online))
+        .change_context(SendTestAlertError)?;
+
+    println!(
+        "Test alert sent to channel {}",
+        service.config().alert_channel_id()
+    );
+    Ok(())
+}
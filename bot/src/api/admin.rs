@@ -0,0 +1,226 @@
+use actix_web::{web, HttpResponse};
+use futures::future::{err, ok, Ready};
+use memobot_kernel::{Kernel, ShutdownReason};
+use serde::{Deserialize, Serialize};
+
+use super::health::shard_reports;
+use crate::bot::commands::{self, CommandRegistry, SyncSummary};
+use crate::bot::ShardStates;
+use crate::util::tracing::LogFilter;
+
+#[derive(Debug)]
+pub enum AdminAuthorizationError {
+    InvalidToken,
+    NoAdminToken,
+}
+
+impl std::fmt::Display for AdminAuthorizationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("failed to authorize admin")
+    }
+}
+
+impl actix_web::ResponseError for AdminAuthorizationError {
+    fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
+        match self {
+            AdminAuthorizationError::InvalidToken => {
+                HttpResponse::Unauthorized().body("401 Unauthorized")
+            }
+            AdminAuthorizationError::NoAdminToken => HttpResponse::NotFound().body("404 Not Found"),
+        }
+    }
+}
+
+/// Proof that the request has a valid admin bearer token
+/// set with `MEMOBOT_API_ADMIN_TOKEN`.
+pub struct AdminAuthorization(Kernel);
+
+impl AdminAuthorization {
+    #[must_use]
+    pub fn kernel(&self) -> &Kernel {
+        &self.0
+    }
+}
+
+impl actix_web::FromRequest for AdminAuthorization {
+    type Error = AdminAuthorizationError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(
+        req: &actix_web::HttpRequest,
+        _payload: &mut actix_web::dev::Payload,
+    ) -> Self::Future {
+        let Some(kernel) = req.app_data::<web::Data<Kernel>>() else {
+            return err(AdminAuthorizationError::NoAdminToken);
+        };
+
+        let config = kernel.config();
+        let Some(actual_token) = config.api().admin_token() else {
+            tracing::warn!("user tried to access admin API while it is disabled");
+            return err(AdminAuthorizationError::NoAdminToken);
+        };
+
+        if !memobot_kernel::auth::has_bearer_token(req, actual_token) {
+            tracing::warn!("user tried to access admin API with invalid token");
+            return err(AdminAuthorizationError::InvalidToken);
+        }
+
+        ok(AdminAuthorization(kernel.as_ref().clone()))
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct TaskReport {
+    id: u64,
+    blocking: bool,
+    location: String,
+    elapsed_ms: u128,
+}
+
+#[derive(Debug, Serialize)]
+struct CommandSyncReport {
+    scope: String,
+    #[serde(flatten)]
+    summary: SyncSummary,
+}
+
+#[derive(Debug, Serialize)]
+struct LogFilterReport {
+    filter: String,
+    revert_to: Option<String>,
+    revert_in_secs: Option<u64>,
+}
+
+impl LogFilterReport {
+    fn new(log_filter: &LogFilter) -> Self {
+        let revert = log_filter.pending_revert();
+        Self {
+            filter: log_filter.current(),
+            revert_in_secs: revert.as_ref().map(|v| {
+                v.at.saturating_duration_since(std::time::Instant::now())
+                    .as_secs()
+            }),
+            revert_to: revert.map(|v| v.directives),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LogFilterParams {
+    /// Directives in the same format as `RUST_LOG`.
+    pub filter: String,
+    /// Changes the filter back after this many seconds.
+    pub revert_after_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ShutdownParams {
+    pub reason: Option<String>,
+}
+
+pub async fn list_shards(
+    _authorization: AdminAuthorization,
+    states: web::Data<ShardStates>,
+) -> HttpResponse {
+    HttpResponse::Ok().json(shard_reports(&states))
+}
+
+pub async fn get_log_filter(
+    _authorization: AdminAuthorization,
+    log_filter: web::Data<LogFilter>,
+) -> HttpResponse {
+    HttpResponse::Ok().json(LogFilterReport::new(&log_filter))
+}
+
+#[tracing::instrument(skip_all, fields(
+    params.filter = %params.filter,
+    params.revert_after_secs = ?params.revert_after_secs,
+))]
+pub async fn set_log_filter(
+    authorization: AdminAuthorization,
+    log_filter: web::Data<LogFilter>,
+    params: web::Json<LogFilterParams>,
+) -> HttpResponse {
+    let result = match params.revert_after_secs {
+        Some(secs) => log_filter.set_for(
+            authorization.kernel(),
+            &params.filter,
+            std::time::Duration::from_secs(secs),
+        ),
+        None => log_filter.set(&params.filter),
+    };
+
+    if let Err(error) = result {
+        tracing::warn!(?error, "Failed to change log filter from the admin API");
+        return HttpResponse::BadRequest().body("400 Bad Request: invalid log filter");
+    }
+
+    tracing::info!("Log filter changed to {:?}", params.filter);
+    HttpResponse::Ok().json(LogFilterReport::new(&log_filter))
+}
+
+pub async fn list_tasks(authorization: AdminAuthorization) -> HttpResponse {
+    let tasks = authorization
+        .kernel()
+        .background_tasks()
+        .into_iter()
+        .map(|task| TaskReport {
+            id: task.id,
+            blocking: task.blocking,
+            location: task.location.to_string(),
+            elapsed_ms: task.elapsed().as_millis(),
+        })
+        .collect::<Vec<_>>();
+
+    HttpResponse::Ok().json(tasks)
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn register_commands(
+    authorization: AdminAuthorization,
+    registry: web::Data<CommandRegistry>,
+) -> HttpResponse {
+    match commands::sync(authorization.kernel(), &registry).await {
+        Ok(summaries) => {
+            let reports = summaries
+                .into_iter()
+                .map(|(scope, summary)| CommandSyncReport {
+                    scope: scope.to_string(),
+                    summary,
+                })
+                .collect::<Vec<_>>();
+
+            HttpResponse::Ok().json(reports)
+        }
+        Err(error) => {
+            tracing::error!(?error, "Failed to register commands from the admin API");
+            HttpResponse::InternalServerError().body("500 Internal Server Error")
+        }
+    }
+}
+
+/// The request body is optional, a shutdown without a reason can
+/// be requested with an empty body.
+#[tracing::instrument(skip_all)]
+pub async fn shutdown(
+    authorization: AdminAuthorization,
+    params: Option<web::Json<ShutdownParams>>,
+) -> HttpResponse {
+    let reason = params.and_then(|v| v.into_inner().reason);
+    authorization
+        .kernel()
+        .shutdown(ShutdownReason::Admin(reason));
+    HttpResponse::Accepted().body("Shutting down")
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/admin")
+            .route("/commands", web::post().to(register_commands))
+            .route("/log-filter", web::get().to(get_log_filter))
+            .route("/log-filter", web::put().to(set_log_filter))
+            .route("/shards", web::get().to(list_shards))
+            .route("/shutdown", web::post().to(shutdown))
+            .route("/tasks", web::get().to(list_tasks)),
+    );
+}
@@ -0,0 +1,67 @@
+use actix_web::{web, HttpResponse};
+use memobot_kernel::Kernel;
+use serde::Serialize;
+
+use crate::bot::{ShardStates, ShardStatus};
+
+#[derive(Debug, Serialize)]
+struct HealthReport {
+    status: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct ShardReport {
+    id: u64,
+    status: ShardStatus,
+    latency_ms: Option<u128>,
+    restarts: u32,
+}
+
+#[derive(Debug, Serialize)]
+struct ReadinessReport {
+    ready: bool,
+    shutting_down: bool,
+    shards: Vec<ShardReport>,
+}
+
+/// Always responds as long as the process is alive.
+pub async fn healthz() -> HttpResponse {
+    HttpResponse::Ok().json(HealthReport { status: "ok" })
+}
+
+/// Responds with `200 OK` if every shard is connected and the
+/// kernel is not shutting down, `503 Service Unavailable` otherwise.
+pub async fn readyz(kernel: web::Data<Kernel>, states: web::Data<ShardStates>) -> HttpResponse {
+    let shutting_down = kernel.is_shutdown();
+    let ready = !shutting_down && states.all_connected();
+
+    let report = ReadinessReport {
+        ready,
+        shutting_down,
+        shards: shard_reports(&states),
+    };
+
+    if ready {
+        HttpResponse::Ok().json(report)
+    } else {
+        HttpResponse::ServiceUnavailable().json(report)
+    }
+}
+
+pub(super) fn shard_reports(states: &ShardStates) -> Vec<ShardReport> {
+    states
+        .snapshot()
+        .into_iter()
+        .map(|(id, state)| ShardReport {
+            id,
+            status: state.status,
+            latency_ms: state.latency.map(|v| v.as_millis()),
+            restarts: state.restarts,
+        })
+        .collect()
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route("/healthz", web::get().to(healthz))
+        .route("/readyz", web::get().to(readyz));
+}
@@ -0,0 +1,15 @@
+use actix_web::{web, HttpResponse};
+use memobot_kernel::Kernel;
+
+/// Responds with all metrics in the Prometheus text format.
+pub async fn metrics(kernel: web::Data<Kernel>) -> HttpResponse {
+    let body = kernel.metrics().encode(kernel.remaining_background_tasks());
+
+    HttpResponse::Ok()
+        .content_type(prometheus::TEXT_FORMAT)
+        .body(body)
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route("/metrics", web::get().to(metrics));
+}
@@ -1 +1,3 @@
-// pub async fn start(kernel: Kernel, paradise: Option<ParadiseService>) {}
+pub mod admin;
+pub mod health;
+pub mod metrics;
@@ -0,0 +1,15 @@
+pub use memobot_kernel::command::{Command, CommandError, CommandRegistry, CommandScope};
+
+mod ping;
+mod sync;
+
+pub use sync::{fetch, global_scope, purge, sync, sync_to, CommandSyncError, SyncSummary};
+
+/// Creates a [`CommandRegistry`] with all of memobot's built-in
+/// commands registered.
+#[must_use]
+pub fn registry() -> CommandRegistry {
+    let mut registry = CommandRegistry::new();
+    registry.register::<ping::Ping>();
+    registry
+}
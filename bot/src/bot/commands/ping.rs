@@ -0,0 +1,38 @@
+use async_trait::async_trait;
+use error_stack::{FutureExt, Result};
+use std::future::IntoFuture;
+use twilight_interactions::command::{CommandModel, CreateCommand};
+use twilight_model::application::interaction::Interaction;
+use twilight_model::http::interaction::{InteractionResponse, InteractionResponseType};
+use twilight_util::builder::InteractionResponseDataBuilder;
+
+use super::{Command, CommandError};
+use crate::bot::Context;
+
+#[derive(Debug, CommandModel, CreateCommand)]
+#[command(name = "ping", desc = "Checks if the bot is alive")]
+pub struct Ping;
+
+#[async_trait]
+impl Command for Ping {
+    async fn run(self, ctx: &Context, interaction: &Interaction) -> Result<(), CommandError> {
+        let response = InteractionResponse {
+            kind: InteractionResponseType::ChannelMessageWithSource,
+            data: Some(
+                InteractionResponseDataBuilder::new()
+                    .content("Pong!")
+                    .build(),
+            ),
+        };
+
+        ctx.kernel()
+            .interaction()
+            .await
+            .create_response(interaction.id, &interaction.token, &response)
+            .into_future()
+            .change_context(CommandError)
+            .await?;
+
+        Ok(())
+    }
+}
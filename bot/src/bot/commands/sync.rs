@@ -0,0 +1,624 @@
+use derive_more::Display;
+use error_stack::{FutureExt, Result, ResultExt};
+use futures::TryFutureExt;
+use memobot_kernel::{Config, Environment, Kernel};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::IntoFuture;
+use twilight_http::client::InteractionClient;
+use twilight_http::request::application::command::create_global_command::CreateGlobalChatInputCommand;
+use twilight_http::request::application::command::create_guild_command::CreateGuildChatInputCommand;
+use twilight_model::application::command::{Command, CommandOption};
+use twilight_model::guild::Permissions;
+use twilight_model::id::marker::{CommandMarker, GuildMarker};
+use twilight_model::id::Id;
+
+use super::{CommandRegistry, CommandScope};
+
+/// Key of the guild that global commands were last registered
+/// to in the kernel state, see [`purge_previous_dev_guild`].
+const DEV_GUILD_STATE_KEY: &str = "commands_dev_guild_id";
+
+#[derive(Debug, Display)]
+#[display(fmt = "Could not synchronize commands with Discord")]
+pub struct CommandSyncError;
+impl error_stack::Context for CommandSyncError {}
+
+/// Changes made to a scope's commands after a [`sync`].
+#[derive(Debug, Default, Serialize)]
+pub struct SyncSummary {
+    pub created: Vec<String>,
+    pub updated: Vec<String>,
+    pub deleted: Vec<String>,
+    pub unchanged: usize,
+}
+
+impl SyncSummary {
+    #[must_use]
+    pub fn has_changes(&self) -> bool {
+        !self.created.is_empty() || !self.updated.is_empty() || !self.deleted.is_empty()
+    }
+}
+
+/// Compares the commands in the registry against the ones Discord
+/// has for the current application and only creates, updates or
+/// deletes commands that are different.
+///
+/// Global commands are always synchronized, guild commands are only
+/// synchronized for guilds that have at least one registered command.
+///
+/// If the bot is not running in production, global commands are
+/// registered to `MEMOBOT_DEV_GUILD_ID` instead so changes propagate
+/// instantly and production commands are left alone. Commands left in
+/// a previous development guild are deleted.
+#[tracing::instrument(skip_all)]
+pub async fn sync(
+    kernel: &Kernel,
+    registry: &CommandRegistry,
+) -> Result<Vec<(CommandScope, SyncSummary)>, CommandSyncError> {
+    let global_scope = global_scope(&kernel.config())?;
+    purge_previous_dev_guild(kernel, registry, global_scope).await?;
+    sync_to(kernel, registry, global_scope).await
+}
+
+/// Same as [`sync`] but registers global commands to `global_scope`.
+#[tracing::instrument(skip(kernel, registry))]
+pub async fn sync_to(
+    kernel: &Kernel,
+    registry: &CommandRegistry,
+    global_scope: CommandScope,
+) -> Result<Vec<(CommandScope, SyncSummary)>, CommandSyncError> {
+    let interaction = kernel.interaction().await;
+
+    let mut scopes: Vec<(CommandScope, Vec<Command>)> = vec![(global_scope, Vec::new())];
+    for scope in registry.scopes() {
+        let definitions = registry.definitions_with_scope(scope).cloned();
+        let scope = match scope {
+            CommandScope::Global => global_scope,
+            scope => scope,
+        };
+
+        match scopes.iter_mut().find(|(v, _)| *v == scope) {
+            Some((_, local)) => local.extend(definitions),
+            None => scopes.push((scope, definitions.collect())),
+        }
+    }
+
+    let mut summaries = Vec::new();
+    for (scope, local) in scopes {
+        let summary = sync_scope(&interaction, scope, &local)
+            .await
+            .attach_printable_lazy(|| format!("while synchronizing {scope} commands"))?;
+
+        if summary.has_changes() {
+            tracing::info!(
+                created = ?summary.created,
+                updated = ?summary.updated,
+                deleted = ?summary.deleted,
+                unchanged = %summary.unchanged,
+                "Synchronized {scope} commands"
+            );
+        } else {
+            tracing::info!(
+                "All {} {scope} command(s) are up to date",
+                summary.unchanged
+            );
+        }
+        summaries.push((scope, summary));
+    }
+
+    Ok(summaries)
+}
+
+/// Deletes the commands left in the guild that global commands were
+/// registered to before `MEMOBOT_DEV_GUILD_ID` was changed, unless
+/// that guild has registered commands of its own.
+///
+/// The guild is remembered until its commands are purged, so a failed
+/// purge is retried the next time commands are synchronized.
+async fn purge_previous_dev_guild(
+    kernel: &Kernel,
+    registry: &CommandRegistry,
+    global_scope: CommandScope,
+) -> Result<(), CommandSyncError> {
+    let previous = kernel
+        .state(DEV_GUILD_STATE_KEY)
+        .await
+        .change_context(CommandSyncError)
+        .attach_printable("failed to read the previous development guild")?
+        .and_then(|v| v.parse::<Id<GuildMarker>>().ok())
+        .map(CommandScope::Guild);
+
+    if previous == Some(global_scope) {
+        return Ok(());
+    }
+
+    if let Some(scope) = previous.filter(|v| !registry.scopes().any(|scope| scope == *v)) {
+        if let Err(error) = purge(kernel, scope).await {
+            tracing::warn!(
+                ?error,
+                "Failed to purge commands of the previous development guild"
+            );
+            return Ok(());
+        }
+    }
+
+    let current = match global_scope {
+        CommandScope::Global => None,
+        CommandScope::Guild(guild_id) => Some(guild_id.to_string()),
+    };
+    kernel
+        .set_state(DEV_GUILD_STATE_KEY, current.as_deref())
+        .await
+        .change_context(CommandSyncError)
+        .attach_printable("failed to store the development guild")
+}
+
+/// Commands Discord has for the current application under `scope`.
+pub async fn fetch(kernel: &Kernel, scope: CommandScope) -> Result<Vec<Command>, CommandSyncError> {
+    let interaction = kernel.interaction().await;
+    fetch_commands(&interaction, scope).await
+}
+
+/// Deletes every command under `scope` from Discord and
+/// returns the names of the deleted commands.
+#[tracing::instrument(skip(kernel))]
+pub async fn purge(kernel: &Kernel, scope: CommandScope) -> Result<Vec<String>, CommandSyncError> {
+    let interaction = kernel.interaction().await;
+    let remote = fetch_commands(&interaction, scope).await?;
+
+    let mut deleted = Vec::new();
+    for command in remote {
+        let Some(id) = command.id else {
+            continue;
+        };
+        delete_command(&interaction, scope, id).await?;
+        deleted.push(command.name);
+    }
+
+    tracing::info!(?deleted, "Purged {scope} commands");
+    Ok(deleted)
+}
+
+/// Where global commands should be registered to, depending
+/// on the environment the bot is running in.
+///
+/// Outside of production global commands are only ever registered to
+/// `MEMOBOT_DEV_GUILD_ID`, so a missing guild is an error instead of
+/// overwriting the production commands.
+pub fn global_scope(config: &Config) -> Result<CommandScope, CommandSyncError> {
+    match (config.environment(), config.dev_guild_id()) {
+        (Environment::Production, _) => Ok(CommandScope::Global),
+        (_, Some(guild_id)) => Ok(CommandScope::Guild(guild_id)),
+        (environment, None) => Err(CommandSyncError).attach_printable(format!(
+            "MEMOBOT_DEV_GUILD_ID must be set to register commands in {environment}"
+        )),
+    }
+}
+
+async fn sync_scope(
+    interaction: &InteractionClient<'_>,
+    scope: CommandScope,
+    local: &[Command],
+) -> Result<SyncSummary, CommandSyncError> {
+    let remote = fetch_commands(interaction, scope).await?;
+    let mut remote = remote
+        .into_iter()
+        .map(|v| (v.name.clone(), v))
+        .collect::<HashMap<_, _>>();
+
+    let mut summary = SyncSummary::default();
+    for command in local {
+        match remote.remove(&command.name) {
+            Some(existing) if is_same_command(scope, command, &existing) => {
+                summary.unchanged += 1;
+            }
+            Some(existing) => {
+                match existing.id {
+                    Some(id) if is_editable(scope, command, &existing) => {
+                        update_command(interaction, scope, id, command).await?;
+                    }
+                    _ => upsert_command(interaction, scope, command).await?,
+                }
+                summary.updated.push(command.name.clone());
+            }
+            None => {
+                upsert_command(interaction, scope, command).await?;
+                summary.created.push(command.name.clone());
+            }
+        }
+    }
+
+    for (name, command) in remote {
+        let Some(id) = command.id else {
+            continue;
+        };
+        delete_command(interaction, scope, id).await?;
+        summary.deleted.push(name);
+    }
+
+    Ok(summary)
+}
+
+async fn fetch_commands(
+    interaction: &InteractionClient<'_>,
+    scope: CommandScope,
+) -> Result<Vec<Command>, CommandSyncError> {
+    let request = match scope {
+        CommandScope::Global => interaction.global_commands().into_future(),
+        CommandScope::Guild(guild_id) => interaction.guild_commands(guild_id).into_future(),
+    };
+
+    request
+        .change_context(CommandSyncError)
+        .and_then(|v| v.models().change_context(CommandSyncError))
+        .await
+        .attach_printable("failed to fetch commands from Discord")
+}
+
+/// Edits the command with `id` in place so it keeps its ID.
+///
+/// Only the fields checked by [`is_editable`] can be edited.
+async fn update_command(
+    interaction: &InteractionClient<'_>,
+    scope: CommandScope,
+    id: Id<CommandMarker>,
+    command: &Command,
+) -> Result<(), CommandSyncError> {
+    let nsfw = command.nsfw.unwrap_or(false);
+    let request = match scope {
+        CommandScope::Global => interaction
+            .update_global_command(id)
+            .description(&command.description)
+            .command_options(&command.options)
+            .nsfw(nsfw)
+            .into_future(),
+        CommandScope::Guild(guild_id) => interaction
+            .update_guild_command(guild_id, id)
+            .description(&command.description)
+            .command_options(&command.options)
+            .nsfw(nsfw)
+            .into_future(),
+    };
+
+    request
+        .change_context(CommandSyncError)
+        .await
+        .attach_printable_lazy(|| format!("failed to update command {:?}", command.name))?;
+
+    Ok(())
+}
+
+/// Discord overwrites the existing command if a command with
+/// the same name is created so it is used for updates that
+/// [`update_command`] cannot make.
+async fn upsert_command(
+    interaction: &InteractionClient<'_>,
+    scope: CommandScope,
+    command: &Command,
+) -> Result<(), CommandSyncError> {
+    let attach_name = || format!("command: {:?}", command.name);
+    let request = match scope {
+        CommandScope::Global => interaction
+            .create_global_command()
+            .chat_input(&command.name, &command.description)
+            .change_context(CommandSyncError)
+            .and_then(|v| with_command_fields(v, command))
+            .attach_printable_lazy(attach_name)?
+            .into_future(),
+        CommandScope::Guild(guild_id) => interaction
+            .create_guild_command(guild_id)
+            .chat_input(&command.name, &command.description)
+            .change_context(CommandSyncError)
+            .and_then(|v| with_command_fields(v, command))
+            .attach_printable_lazy(attach_name)?
+            .into_future(),
+    };
+
+    request
+        .change_context(CommandSyncError)
+        .await
+        .attach_printable_lazy(attach_name)?;
+
+    Ok(())
+}
+
+/// Sets every field of `command` other than its name and
+/// description on a request that creates it.
+fn with_command_fields<'a, R: CreateChatInputCommand<'a>>(
+    request: R,
+    command: &'a Command,
+) -> Result<R, CommandSyncError> {
+    let mut request = request.command_options(&command.options)?;
+    if let Some(localizations) = &command.name_localizations {
+        request = request.name_localizations(localizations)?;
+    }
+    if let Some(localizations) = &command.description_localizations {
+        request = request.description_localizations(localizations)?;
+    }
+    if let Some(permissions) = command.default_member_permissions {
+        request = request.default_member_permissions(permissions);
+    }
+    if let Some(dm_permission) = command.dm_permission {
+        request = request.dm_permission(dm_permission);
+    }
+    if let Some(nsfw) = command.nsfw {
+        request = request.nsfw(nsfw);
+    }
+    Ok(request)
+}
+
+/// Requests that create a global or a guild chat input command,
+/// so both scopes are built by [`with_command_fields`].
+trait CreateChatInputCommand<'a>: Sized {
+    fn command_options(self, options: &'a [CommandOption]) -> Result<Self, CommandSyncError>;
+
+    fn name_localizations(
+        self,
+        localizations: &'a HashMap<String, String>,
+    ) -> Result<Self, CommandSyncError>;
+
+    fn description_localizations(
+        self,
+        localizations: &'a HashMap<String, String>,
+    ) -> Result<Self, CommandSyncError>;
+
+    fn default_member_permissions(self, permissions: Permissions) -> Self;
+
+    fn dm_permission(self, dm_permission: bool) -> Self;
+
+    fn nsfw(self, nsfw: bool) -> Self;
+}
+
+impl<'a> CreateChatInputCommand<'a> for CreateGlobalChatInputCommand<'a> {
+    fn command_options(self, options: &'a [CommandOption]) -> Result<Self, CommandSyncError> {
+        Self::command_options(self, options).change_context(CommandSyncError)
+    }
+
+    fn name_localizations(
+        self,
+        localizations: &'a HashMap<String, String>,
+    ) -> Result<Self, CommandSyncError> {
+        Self::name_localizations(self, localizations).change_context(CommandSyncError)
+    }
+
+    fn description_localizations(
+        self,
+        localizations: &'a HashMap<String, String>,
+    ) -> Result<Self, CommandSyncError> {
+        Self::description_localizations(self, localizations).change_context(CommandSyncError)
+    }
+
+    fn default_member_permissions(self, permissions: Permissions) -> Self {
+        Self::default_member_permissions(self, permissions)
+    }
+
+    fn dm_permission(self, dm_permission: bool) -> Self {
+        Self::dm_permission(self, dm_permission)
+    }
+
+    fn nsfw(self, nsfw: bool) -> Self {
+        Self::nsfw(self, nsfw)
+    }
+}
+
+impl<'a> CreateChatInputCommand<'a> for CreateGuildChatInputCommand<'a> {
+    fn command_options(self, options: &'a [CommandOption]) -> Result<Self, CommandSyncError> {
+        Self::command_options(self, options).change_context(CommandSyncError)
+    }
+
+    fn name_localizations(
+        self,
+        localizations: &'a HashMap<String, String>,
+    ) -> Result<Self, CommandSyncError> {
+        Self::name_localizations(self, localizations).change_context(CommandSyncError)
+    }
+
+    fn description_localizations(
+        self,
+        localizations: &'a HashMap<String, String>,
+    ) -> Result<Self, CommandSyncError> {
+        Self::description_localizations(self, localizations).change_context(CommandSyncError)
+    }
+
+    fn default_member_permissions(self, permissions: Permissions) -> Self {
+        Self::default_member_permissions(self, permissions)
+    }
+
+    // DM permission only applies to global commands
+    fn dm_permission(self, _dm_permission: bool) -> Self {
+        self
+    }
+
+    fn nsfw(self, nsfw: bool) -> Self {
+        Self::nsfw(self, nsfw)
+    }
+}
+
+async fn delete_command(
+    interaction: &InteractionClient<'_>,
+    scope: CommandScope,
+    id: Id<CommandMarker>,
+) -> Result<(), CommandSyncError> {
+    let request = match scope {
+        CommandScope::Global => interaction.delete_global_command(id).into_future(),
+        CommandScope::Guild(guild_id) => {
+            interaction.delete_guild_command(guild_id, id).into_future()
+        }
+    };
+
+    request
+        .change_context(CommandSyncError)
+        .await
+        .attach_printable_lazy(|| format!("failed to delete command {id}"))?;
+
+    Ok(())
+}
+
+/// Discord fills in default values for some of the fields
+/// so both sides are normalized before comparing them.
+fn is_same_command(scope: CommandScope, local: &Command, remote: &Command) -> bool {
+    is_editable(scope, local, remote)
+        && local.description == remote.description
+        && local.nsfw.unwrap_or(false) == remote.nsfw.unwrap_or(false)
+        && normalize_options(&local.options) == normalize_options(&remote.options)
+}
+
+/// Whether `remote` only differs from `local` in fields that
+/// twilight's edit command requests can change.
+fn is_editable(scope: CommandScope, local: &Command, remote: &Command) -> bool {
+    let same_dm_permission = match scope {
+        // DM permission only applies to global commands
+        CommandScope::Global => {
+            local.dm_permission.unwrap_or(true) == remote.dm_permission.unwrap_or(true)
+        }
+        CommandScope::Guild(..) => true,
+    };
+
+    local.kind == remote.kind
+        && local.name == remote.name
+        && is_same_localizations(&local.name_localizations, &remote.name_localizations)
+        && is_same_localizations(
+            &local.description_localizations,
+            &remote.description_localizations,
+        )
+        && local.default_member_permissions == remote.default_member_permissions
+        && same_dm_permission
+}
+
+fn is_same_localizations(
+    local: &Option<HashMap<String, String>>,
+    remote: &Option<HashMap<String, String>>,
+) -> bool {
+    let local = local.as_ref().filter(|v| !v.is_empty());
+    let remote = remote.as_ref().filter(|v| !v.is_empty());
+    local == remote
+}
+
+fn normalize_options(options: &[CommandOption]) -> Vec<CommandOption> {
+    options
+        .iter()
+        .map(|option| CommandOption {
+            autocomplete: Some(option.autocomplete.unwrap_or(false)),
+            channel_types: option.channel_types.clone().filter(|v| !v.is_empty()),
+            choices: option.choices.clone().filter(|v| !v.is_empty()),
+            description_localizations: option
+                .description_localizations
+                .clone()
+                .filter(|v| !v.is_empty()),
+            name_localizations: option.name_localizations.clone().filter(|v| !v.is_empty()),
+            options: option
+                .options
+                .as_deref()
+                .map(normalize_options)
+                .filter(|v| !v.is_empty()),
+            required: Some(option.required.unwrap_or(false)),
+            ..option.clone()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use twilight_model::application::command::CommandType;
+    use twilight_util::builder::command::{CommandBuilder, StringBuilder, SubCommandBuilder};
+
+    fn local() -> Command {
+        CommandBuilder::new("remind", "Reminds you later", CommandType::ChatInput)
+            .option(
+                SubCommandBuilder::new("me", "Reminds you")
+                    .option(StringBuilder::new("message", "What to remind").required(true))
+                    .option(StringBuilder::new("when", "When to remind")),
+            )
+            .build()
+    }
+
+    /// Same command as Discord returns it, with default values filled in.
+    fn remote() -> Command {
+        let mut command = local();
+        command.id = Some(Id::new(1));
+        command.dm_permission = Some(true);
+        command.nsfw = Some(false);
+        command.name_localizations = Some(HashMap::new());
+
+        let subcommand = &mut command.options[0];
+        subcommand.autocomplete = Some(false);
+        subcommand.options.as_mut().unwrap()[1].required = Some(false);
+        command
+    }
+
+    #[test]
+    fn same_command_with_discord_defaults() {
+        assert!(is_same_command(CommandScope::Global, &local(), &remote()));
+    }
+
+    #[test]
+    fn different_description() {
+        let mut remote = remote();
+        remote.description = "Reminds you".into();
+
+        assert!(!is_same_command(CommandScope::Global, &local(), &remote));
+        assert!(is_editable(CommandScope::Global, &local(), &remote));
+    }
+
+    #[test]
+    fn different_nested_option() {
+        let mut remote = remote();
+        remote.options[0].options.as_mut().unwrap()[1].required = Some(true);
+
+        assert!(!is_same_command(CommandScope::Global, &local(), &remote));
+        assert!(is_editable(CommandScope::Global, &local(), &remote));
+    }
+
+    #[test]
+    fn different_permissions_are_not_editable() {
+        let mut remote = remote();
+        remote.default_member_permissions = Some(Permissions::ADMINISTRATOR);
+
+        assert!(!is_same_command(CommandScope::Global, &local(), &remote));
+        assert!(!is_editable(CommandScope::Global, &local(), &remote));
+    }
+
+    #[test]
+    fn dm_permission_only_applies_to_global_commands() {
+        let mut remote = remote();
+        remote.dm_permission = Some(false);
+
+        let guild = CommandScope::Guild(Id::new(1));
+        assert!(!is_same_command(CommandScope::Global, &local(), &remote));
+        assert!(is_same_command(guild, &local(), &remote));
+    }
+
+    #[test]
+    fn normalize_empty_and_missing_options() {
+        let mut remote = local().options;
+        remote[0].options.as_mut().unwrap()[0].choices = Some(Vec::new());
+        remote[0].options.as_mut().unwrap()[0].name_localizations = Some(HashMap::new());
+
+        assert_eq!(
+            normalize_options(&local().options),
+            normalize_options(&remote)
+        );
+    }
+    #[test]
+    fn global_scope_outside_production_requires_dev_guild() {
+        std::env::set_var("DISCORD_TOKEN", "test");
+        std::env::remove_var("MEMOBOT_DEV_GUILD_ID");
+
+        std::env::set_var("MEMOBOT_ENV", "development");
+        let config = Config::from_env().unwrap();
+        assert!(global_scope(&config).is_err());
+
+        std::env::set_var("MEMOBOT_DEV_GUILD_ID", "1");
+        let config = Config::from_env().unwrap();
+        assert_eq!(
+            global_scope(&config).unwrap(),
+            CommandScope::Guild(Id::new(1))
+        );
+
+        std::env::set_var("MEMOBOT_ENV", "production");
+        let config = Config::from_env().unwrap();
+        assert_eq!(global_scope(&config).unwrap(), CommandScope::Global);
+    }
+}
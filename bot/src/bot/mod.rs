@@ -1,4 +1,8 @@
-mod context;
+pub use memobot_kernel::Context;
 
-pub use context::Context;
+mod states;
+
+pub use states::{ShardState, ShardStates, ShardStatus};
+pub mod commands;
+pub mod session;
 pub mod shard;
@@ -0,0 +1,162 @@
+use derive_more::Display;
+use error_stack::{Result, ResultExt};
+use memobot_kernel::Kernel;
+use std::collections::HashMap;
+use twilight_gateway::{Session, ShardId};
+
+/// Discord only keeps sessions resumable for a short time after
+/// the connection is closed, older sessions are not worth trying.
+const MAX_SESSION_AGE_SECS: i64 = 5 * 60;
+
+#[derive(Debug, Display)]
+#[display(fmt = "Could not access saved gateway sessions")]
+pub struct SessionStoreError;
+impl error_stack::Context for SessionStoreError {}
+
+/// Gateway session of a shard that was saved during shutdown.
+#[derive(Debug, Clone)]
+pub struct SavedSession {
+    /// Resume gateway URL Discord gave for the session, the
+    /// session has to be resumed through it.
+    pub resume_url: String,
+    pub session: Session,
+}
+
+/// Saves the shard's session so it can be resumed on the next boot.
+pub async fn save(
+    kernel: &Kernel,
+    shard_id: ShardId,
+    session: &Session,
+    resume_url: &str,
+) -> Result<(), SessionStoreError> {
+    sqlx::query(
+        "INSERT OR REPLACE INTO gateway_sessions
+        (shard_id, shard_total, session_id, sequence, resume_url)
+        VALUES (?, ?, ?, ?, ?)",
+    )
+    .bind(shard_id.number() as i64)
+    .bind(shard_id.total() as i64)
+    .bind(session.id())
+    .bind(session.sequence() as i64)
+    .bind(resume_url)
+    .execute(kernel.database())
+    .await
+    .change_context(SessionStoreError)?;
+
+    Ok(())
+}
+
+/// Takes every saved session that can still be resumed, keyed by
+/// shard number. Saved sessions are removed afterwards since they
+/// can only be resumed once.
+pub async fn take_all(
+    kernel: &Kernel,
+    shard_total: u64,
+) -> Result<HashMap<u64, SavedSession>, SessionStoreError> {
+    let mut tx = kernel
+        .database()
+        .begin()
+        .await
+        .change_context(SessionStoreError)?;
+
+    let rows: Vec<(i64, String, i64, String)> = sqlx::query_as(
+        "SELECT shard_id, session_id, sequence, resume_url FROM gateway_sessions
+        WHERE shard_total = ? AND saved_at >= datetime('now', ?)",
+    )
+    .bind(shard_total as i64)
+    .bind(format!("-{MAX_SESSION_AGE_SECS} seconds"))
+    .fetch_all(&mut *tx)
+    .await
+    .change_context(SessionStoreError)?;
+
+    sqlx::query("DELETE FROM gateway_sessions")
+        .execute(&mut *tx)
+        .await
+        .change_context(SessionStoreError)?;
+
+    tx.commit().await.change_context(SessionStoreError)?;
+
+    let sessions = rows
+        .into_iter()
+        .map(|(shard_id, session_id, sequence, resume_url)| {
+            let session = Session::new(sequence as u64, session_id);
+            (
+                shard_id as u64,
+                SavedSession {
+                    resume_url,
+                    session,
+                },
+            )
+        })
+        .collect();
+
+    Ok(sessions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use memobot_kernel::Config;
+
+    /// Kernel with an in-memory database that never talks to Discord.
+    async fn kernel() -> Kernel {
+        std::env::set_var("DISCORD_TOKEN", "test");
+        std::env::set_var("MEMOBOT_APPLICATION_ID", "1");
+        std::env::set_var("MEMOBOT_DATABASE_URL", "sqlite::memory:");
+        // In-memory databases are per connection
+        std::env::set_var("MEMOBOT_DATABASE_MAX_CONNECTIONS", "1");
+
+        let config = Config::from_env().expect("invalid test config");
+        Kernel::init(config).await.expect("failed to init kernel")
+    }
+
+    async fn save_session(kernel: &Kernel, shard: u64, total: u64) {
+        let session = Session::new(42, format!("session-{shard}"));
+        let resume_url = format!("wss://resume-{shard}.discord.gg");
+        save(kernel, ShardId::new(shard, total), &session, &resume_url)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn take_all_returns_saved_sessions_once() {
+        let kernel = kernel().await;
+        save_session(&kernel, 0, 2).await;
+        save_session(&kernel, 1, 2).await;
+
+        let sessions = take_all(&kernel, 2).await.unwrap();
+        assert_eq!(sessions.len(), 2);
+
+        let saved = &sessions[&1];
+        assert_eq!(saved.resume_url, "wss://resume-1.discord.gg");
+        assert_eq!(saved.session.id(), "session-1");
+        assert_eq!(saved.session.sequence(), 42);
+
+        assert!(take_all(&kernel, 2).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn take_all_discards_other_shard_totals() {
+        let kernel = kernel().await;
+        save_session(&kernel, 0, 1).await;
+
+        assert!(take_all(&kernel, 2).await.unwrap().is_empty());
+        assert!(take_all(&kernel, 1).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn take_all_discards_expired_sessions() {
+        let kernel = kernel().await;
+        save_session(&kernel, 0, 2).await;
+        save_session(&kernel, 1, 2).await;
+
+        sqlx::query("UPDATE gateway_sessions SET saved_at = datetime('now', ?) WHERE shard_id = 0")
+            .bind(format!("-{} seconds", MAX_SESSION_AGE_SECS + 1))
+            .execute(kernel.database())
+            .await
+            .unwrap();
+
+        let sessions = take_all(&kernel, 2).await.unwrap();
+        assert_eq!(sessions.keys().collect::<Vec<_>>(), [&1]);
+    }
+}
@@ -1,16 +1,27 @@
 use futures::future::Either;
-use memobot_kernel::{Kernel, ShutdownReason};
+use memobot_kernel::{ExtensionRegistry, Kernel};
+use std::future::Future;
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
 use tokio_util::task::TaskTracker;
 use twilight_gateway::Event;
-use twilight_gateway::{error::ReceiveMessageErrorType, CloseFrame, Shard};
+use twilight_gateway::{error::ReceiveMessageErrorType, CloseFrame, Session, Shard};
+use twilight_model::gateway::CloseCode;
 
-use crate::bot::Context;
+use crate::bot::commands::{self, CommandRegistry};
+use crate::bot::session;
+use crate::bot::{Context, ShardStates};
 
 #[tracing::instrument(skip_all, fields(
     event.guild_id = ?event.guild_id(),
     event.kind = ?event.kind(),
 ))]
 pub async fn process_event(ctx: Context, event: Event) {
+    ctx.kernel()
+        .metrics()
+        .record_gateway_event(ctx.shard_id(), event.kind());
+    ctx.extensions().on_event(&ctx, &event).await;
+
     match event {
         Event::Ready(info) => {
             tracing::info!("Logged in as {} ({})", info.user.name, info.user.id);
@@ -27,42 +38,195 @@ pub async fn process_event(ctx: Context, event: Event) {
                 );
                 ctx.kernel().override_application_id(new_app_id).await;
             }
+
+            sync_commands_once(&ctx).await;
+        }
+        // A shard resuming a saved session never receives Ready.
+        Event::Resumed => sync_commands_once(&ctx).await,
+        Event::InteractionCreate(interaction) => {
+            ctx.commands().dispatch(ctx.clone(), interaction.0).await;
         }
         _ => {}
     }
 }
 
+/// Synchronizes commands from shard 0 if it has not been done yet.
+///
+/// Every shard receives its own Ready or Resumed event, again after
+/// each new connection, but commands only need to be synchronized once.
+/// Failed attempts are retried on the next Ready or Resumed event.
+async fn sync_commands_once(ctx: &Context) {
+    if ctx.shard_id().number() != 0 || !ctx.commands().mark_synced() {
+        return;
+    }
+
+    if let Err(error) = commands::sync(ctx.kernel(), ctx.commands()).await {
+        tracing::error!(?error, "Failed to register commands");
+        ctx.commands().unmark_synced();
+    }
+}
+
+/// Signals from the shutdown phases to every running shard.
+#[derive(Debug, Clone, Default)]
+pub struct ShardShutdown {
+    drain: CancellationToken,
+    drained: TaskTracker,
+    close: CancellationToken,
+}
+
+impl ShardShutdown {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stops shards from receiving events and waits until they
+    /// have finished handling the events they received.
+    pub async fn drain(&self) {
+        self.drain.cancel();
+        self.drained.close();
+        self.drained.wait().await;
+    }
+
+    /// Lets drained shards disconnect from the gateway.
+    pub fn close(&self) {
+        self.close.cancel();
+    }
+}
+
+/// Why [`main`] has stopped running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShardExit {
+    /// The kernel is shutting down.
+    Shutdown,
+    /// Discord closed the connection and the shard cannot reconnect by itself.
+    FatallyClosed(CloseCode),
+}
+
 #[tracing::instrument(skip_all, fields(shard.id = %shard.id()))]
-pub async fn main(kernel: Kernel, mut shard: Shard) {
-    let context = Context::new(&kernel, shard.id());
+pub async fn main(
+    kernel: Kernel,
+    commands: Arc<CommandRegistry>,
+    extensions: Arc<ExtensionRegistry>,
+    states: ShardStates,
+    shutdown: ShardShutdown,
+    mut shard: Shard,
+) -> ShardExit {
+    let context = Context::new(&kernel, commands, extensions, shard.id());
     let tasks = TaskTracker::new();
+    let drain_token = shutdown.drained.token();
 
-    loop {
-        let action = next_event(&kernel, &mut shard).await;
+    let mut has_connected = false;
+
+    let exit = loop {
+        let action = next_event(&shutdown, &mut shard).await;
         let event = match action {
             ShardAction::Event(e) => e,
             ShardAction::Ignore => continue,
-            ShardAction::CloseLoop => break,
+            ShardAction::CloseLoop(exit) => break exit,
         };
 
+        match &event {
+            Event::Ready(ready) => {
+                states.set_resume_url(shard.id(), Some(ready.resume_gateway_url.clone()));
+                states.update(&shard);
+            }
+            Event::GatewayHello(..) => {
+                // Every Hello after the first one is a new connection.
+                if has_connected {
+                    kernel.metrics().record_shard_reconnect(shard.id());
+                }
+                has_connected = true;
+                states.update(&shard);
+            }
+            Event::GatewayHeartbeatAck => {
+                if let Some(latency) = shard.latency().average() {
+                    kernel.metrics().record_shard_latency(shard.id(), latency);
+                }
+                states.update(&shard);
+            }
+            Event::GatewayClose(..)
+            | Event::GatewayInvalidateSession(..)
+            | Event::GatewayReconnect
+            | Event::Resumed => states.update(&shard),
+            _ => {}
+        }
+
+        kernel.cache().update(&event);
+        kernel.events().publish(shard.id(), &event);
         tasks.spawn(process_event(context.clone(), event));
-    }
+    };
 
     tracing::info!("Closing all shard tasks");
     tasks.close();
-    tasks.wait().await;
+    let closing = async {
+        tasks.wait().await;
+        drop(drain_token);
+
+        if exit == ShardExit::Shutdown {
+            shutdown.close.cancelled().await;
+        }
+    };
+
+    if exit == ShardExit::Shutdown {
+        keep_alive_until(&mut shard, closing).await;
+    } else {
+        closing.await;
+    }
 
-    let is_disconnected = shard.status().is_disconnected();
-    if !is_disconnected {
+    let status = shard.status();
+    if !status.is_disconnected() && !status.is_fatally_closed() {
         tracing::info!("Disconnecting shard...");
-        close_shard(&mut shard).await;
+        let session = close_shard(&mut shard).await;
+
+        let resume_url = states.resume_url(shard.id());
+        if let (Some(session), Some(resume_url)) = (session, resume_url) {
+            tracing::debug!("Saving gateway session");
+            if let Err(error) = session::save(&kernel, shard.id(), &session, &resume_url).await {
+                tracing::warn!(?error, "Failed to save gateway session");
+            }
+        }
     }
+
+    states.update(&shard);
+    exit
 }
 
-async fn close_shard(shard: &mut Shard) {
-    if let Err(error) = shard.close(CloseFrame::NORMAL).await {
-        tracing::error!(?error, "Failed to close shard connection");
+/// Keeps polling the shard until `until` finishes.
+///
+/// Heartbeats are only sent while the shard is polled, so a shard
+/// waiting for the shutdown phases before it can close would get
+/// disconnected by Discord otherwise. Events received in the meantime
+/// are not handled since the shard has already been drained.
+async fn keep_alive_until(shard: &mut Shard, until: impl Future<Output = ()>) {
+    tokio::pin!(until);
+
+    loop {
+        tokio::select! {
+            () = &mut until => break,
+            result = shard.next_message() => match result {
+                Ok(..) => {}
+                Err(source) if source.is_fatal() => {
+                    tracing::error!(?source, "Got fatal shard message error while closing");
+                    until.await;
+                    break;
+                }
+                Err(source) => tracing::warn!(?source, "Got shard message error while closing"),
+            },
+        }
     }
+}
+
+/// Closes the shard while keeping its session resumable and
+/// returns the session if there is one.
+async fn close_shard(shard: &mut Shard) -> Option<Session> {
+    let session = match shard.close(CloseFrame::RESUME).await {
+        Ok(session) => session,
+        Err(error) => {
+            tracing::error!(?error, "Failed to close shard connection");
+            None
+        }
+    };
 
     // Wait until WebSocket connection is FINALLY CLOSED
     loop {
@@ -82,30 +246,36 @@ async fn close_shard(shard: &mut Shard) {
             }
         }
     }
+
+    session
 }
 
 enum ShardAction {
     Event(twilight_gateway::Event),
     Ignore,
-    CloseLoop,
+    CloseLoop(ShardExit),
 }
 
-async fn next_event(app: &Kernel, shard: &mut Shard) -> ShardAction {
+async fn next_event(shutdown: &ShardShutdown, shard: &mut Shard) -> ShardAction {
     use futures::future::select;
 
-    let id = shard.id();
-    match select(Box::pin(shard.next_event()), Box::pin(app.shutdown_guard())).await {
+    match select(
+        Box::pin(shard.next_event()),
+        Box::pin(shutdown.drain.cancelled()),
+    )
+    .await
+    {
         Either::Left((Ok(event), _)) => ShardAction::Event(event),
-        Either::Left((Err(source), _)) => {
-            if source.is_fatal() {
+        Either::Left((Err(source), _)) => match source.kind() {
+            ReceiveMessageErrorType::FatallyClosed { close_code } => {
                 tracing::error!(?source, "Got fatal shard message error");
-                app.shutdown(ShutdownReason::ShardFatalError(id));
-                ShardAction::CloseLoop
-            } else {
+                ShardAction::CloseLoop(ShardExit::FatallyClosed(*close_code))
+            }
+            _ => {
                 tracing::warn!(?source, "Got shard message error");
                 ShardAction::Ignore
             }
-        }
-        Either::Right(_) => ShardAction::CloseLoop,
+        },
+        Either::Right(_) => ShardAction::CloseLoop(ShardExit::Shutdown),
     }
 }
@@ -0,0 +1,119 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use twilight_gateway::{ConnectionStatus, Shard, ShardId};
+
+/// Connection status of a shard, simplified from
+/// [`ConnectionStatus`] for reporting.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShardStatus {
+    Connected,
+    #[default]
+    Disconnected,
+    FatallyClosed,
+    Identifying,
+    Resuming,
+}
+
+impl From<&ConnectionStatus> for ShardStatus {
+    fn from(value: &ConnectionStatus) -> Self {
+        match value {
+            ConnectionStatus::Connected => Self::Connected,
+            ConnectionStatus::Disconnected { .. } => Self::Disconnected,
+            ConnectionStatus::FatallyClosed { .. } => Self::FatallyClosed,
+            ConnectionStatus::Identifying => Self::Identifying,
+            ConnectionStatus::Resuming => Self::Resuming,
+        }
+    }
+}
+
+/// Information about a shard that is tracked by the
+/// shard supervisor.
+#[derive(Debug, Clone, Default)]
+pub struct ShardState {
+    /// Average heartbeat latency, the same value that is
+    /// reported in the metrics.
+    pub latency: Option<Duration>,
+    /// How many times the shard has been restarted since the bot started.
+    pub restarts: u32,
+    /// Resume gateway URL Discord gave for the shard's session, from
+    /// its last Ready event or the saved session it resumed.
+    pub resume_url: Option<String>,
+    pub status: ShardStatus,
+}
+
+/// Shared view of every shard's state.
+#[derive(Debug, Clone, Default)]
+pub struct ShardStates {
+    inner: Arc<RwLock<BTreeMap<u64, ShardState>>>,
+}
+
+impl ShardStates {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn insert(&self, id: ShardId) {
+        self.inner
+            .write()
+            .expect("shard states lock is poisoned")
+            .entry(id.number())
+            .or_default();
+    }
+
+    /// Updates the shard's status and latency from the shard itself.
+    pub(crate) fn update(&self, shard: &Shard) {
+        let mut inner = self.inner.write().expect("shard states lock is poisoned");
+        let state = inner.entry(shard.id().number()).or_default();
+        state.latency = shard.latency().average();
+        state.status = ShardStatus::from(shard.status());
+    }
+
+    /// Sets the resume gateway URL Discord gave for the shard's session.
+    pub fn set_resume_url(&self, id: ShardId, resume_url: Option<String>) {
+        let mut inner = self.inner.write().expect("shard states lock is poisoned");
+        inner.entry(id.number()).or_default().resume_url = resume_url;
+    }
+
+    pub(crate) fn resume_url(&self, id: ShardId) -> Option<String> {
+        self.inner
+            .read()
+            .expect("shard states lock is poisoned")
+            .get(&id.number())
+            .and_then(|v| v.resume_url.clone())
+    }
+
+    /// Increments the shard's restart count and returns the new count.
+    pub(crate) fn record_restart(&self, id: ShardId) -> u32 {
+        let mut inner = self.inner.write().expect("shard states lock is poisoned");
+        let state = inner.entry(id.number()).or_default();
+        state.restarts += 1;
+        state.restarts
+    }
+}
+
+impl ShardStates {
+    /// Copy of every shard's state, ordered by shard id.
+    #[must_use]
+    pub fn snapshot(&self) -> Vec<(u64, ShardState)> {
+        self.inner
+            .read()
+            .expect("shard states lock is poisoned")
+            .iter()
+            .map(|(id, state)| (*id, state.clone()))
+            .collect()
+    }
+
+    /// Whether there is at least one shard and all of them are connected.
+    #[must_use]
+    pub fn all_connected(&self) -> bool {
+        let inner = self.inner.read().expect("shard states lock is poisoned");
+        !inner.is_empty()
+            && inner
+                .values()
+                .all(|v| matches!(v.status, ShardStatus::Connected))
+    }
+}
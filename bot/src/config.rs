@@ -0,0 +1,26 @@
+use memobot_env_vars::EnvVar;
+
+/// Every variable the bot reads, grouped by the config
+/// they belong to.
+#[must_use]
+pub fn sections() -> Vec<(&'static str, Vec<EnvVar>)> {
+    vec![
+        ("kernel", memobot_kernel::Config::env_vars()),
+        ("sentry", memobot_kernel::config::SentryConfig::env_vars()),
+        ("paradise", memobot_paradise::Config::env_vars()),
+        ("logging", crate::util::tracing::env_vars()),
+    ]
+}
+
+/// Warns about values of the configuration file that are not
+/// read by any config, so typos do not go unnoticed.
+pub fn warn_unknown_keys() {
+    let known = sections()
+        .into_iter()
+        .flat_map(|(_, vars)| vars)
+        .collect::<Vec<_>>();
+
+    for origin in memobot_env_vars::unknown_file_keys(&known) {
+        tracing::warn!("Unknown configuration key {origin}");
+    }
+}
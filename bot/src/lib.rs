@@ -1,5 +1,6 @@
 pub mod api;
 pub mod bot;
+pub mod config;
 pub mod sentry;
 pub mod services;
 pub mod util;
@@ -2,9 +2,11 @@ use sentry::ClientInitGuard;
 use std::borrow::Cow;
 
 use memobot_kernel::config::SentryConfig;
-use memobot_kernel::Sensitive;
+use memobot_kernel::{Environment, Sensitive};
 
-pub fn init() -> Option<ClientInitGuard> {
+/// Initializes Sentry if `MEMOBOT_SENTRY_DSN` is set, reporting
+/// events under the environment the bot is configured with.
+pub fn init(environment: Environment) -> Option<ClientInitGuard> {
     let config = match SentryConfig::from_env() {
         Ok(Some(config)) => config,
         Ok(None) => {
@@ -19,7 +21,7 @@ pub fn init() -> Option<ClientInitGuard> {
 
     let opts = sentry::ClientOptions {
         dsn: Some(config.dsn().clone()),
-        environment: Some(Cow::Owned(config.environment().to_string())),
+        environment: Some(Cow::Owned(environment.to_string())),
         release: sentry::release_name!(),
         session_mode: sentry::SessionMode::Request,
         traces_sample_rate: config.traces_sample_rate(),
@@ -28,7 +30,7 @@ pub fn init() -> Option<ClientInitGuard> {
 
     tracing::info!(
         cfg.dsn = ?Sensitive::new(()),
-        cfg.environment = %config.environment(),
+        cfg.environment = %environment,
         opts.release = ?sentry::release_name!(),
         "Sentry integration is enabled"
     );
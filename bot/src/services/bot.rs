@@ -1,25 +1,208 @@
-use memobot_kernel::Kernel;
-use tokio::task::JoinSet;
+use memobot_kernel::{ExtensionRegistry, Kernel, ShutdownPhase, ShutdownReason};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_util::task::TaskTracker;
+use twilight_gateway::{Config, Shard};
+use twilight_model::gateway::CloseCode;
 
-pub async fn start(kernel: Kernel, shards: Vec<twilight_gateway::Shard>) {
-    tracing::info!("Starting bot with {} shard(s)", shards.len());
+use crate::bot::commands::CommandRegistry;
+use crate::bot::shard::{ShardExit, ShardShutdown};
+use crate::bot::ShardStates;
+
+/// If a shard ran longer than this, its previous failures are
+/// not counted as consecutive restarts anymore and the backoff
+/// starts over.
+const STABLE_RUN_DURATION: Duration = Duration::from_secs(10 * 60);
 
-    let mut running_shards = JoinSet::new();
-    let total_shards = shards.len();
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(5 * 60);
 
+/// Spawns a supervisor for every shard and registers the shutdown
+/// hooks that drain and close them.
+///
+/// The returned future finishes once every shard is closed.
+pub fn start(
+    kernel: Kernel,
+    commands: Arc<CommandRegistry>,
+    extensions: Arc<ExtensionRegistry>,
+    states: ShardStates,
+    shards: Vec<Shard>,
+) -> impl Future<Output = ()> {
+    tracing::info!("Starting bot with {} shard(s)", shards.len());
+    tracing::info!("Loaded {} command(s)", commands.len());
+
+    let shutdown = ShardShutdown::new();
+    let supervisors = TaskTracker::new();
     for shard in shards {
-        running_shards.spawn(crate::bot::shard::main(kernel.clone(), shard));
+        states.insert(shard.id());
+        supervisors.spawn(supervise(
+            kernel.clone(),
+            commands.clone(),
+            extensions.clone(),
+            states.clone(),
+            shutdown.clone(),
+            shard,
+        ));
+    }
+    supervisors.close();
+
+    let shutdown_1 = shutdown.clone();
+    kernel.on_shutdown(ShutdownPhase::DrainInteractions, |_| async move {
+        shutdown_1.drain().await;
+    });
+
+    let supervisors_1 = supervisors.clone();
+    kernel.on_shutdown(ShutdownPhase::CloseShards, |_| async move {
+        tracing::info!("Waiting for {} shard(s) to close", supervisors_1.len());
+        shutdown.close();
+        supervisors_1.wait().await;
+    });
+
+    async move {
+        supervisors.wait().await;
+        tracing::info!("Bot service is closed");
     }
+}
+
+/// Runs a shard and restarts it with backoff whenever it stops
+/// unexpectedly, until the kernel is shutting down. The backoff
+/// grows with consecutive restarts up to [`MAX_RESTART_BACKOFF`].
+///
+/// Unrecoverable errors such as an invalid token, an invalid shard
+/// or disallowed intents shut down the entire bot instead.
+#[tracing::instrument(skip_all, fields(shard.id = %shard.id()))]
+async fn supervise(
+    kernel: Kernel,
+    commands: Arc<CommandRegistry>,
+    extensions: Arc<ExtensionRegistry>,
+    states: ShardStates,
+    shutdown: ShardShutdown,
+    shard: Shard,
+) {
+    let id = shard.id();
+    let config = restart_config(shard.config());
 
-    kernel.shutdown_guard().await;
+    let mut shard = Some(shard);
+    let mut consecutive_restarts = 0;
 
-    tracing::info!("Closing bot service...");
     loop {
-        let finished_shards = total_shards - running_shards.len();
-        tracing::info!("Waiting for {finished_shards}/{total_shards} shards(s) to finish",);
+        let shard = shard
+            .take()
+            .unwrap_or_else(|| Shard::with_config(id, config.clone()));
 
-        if running_shards.join_next().await.is_none() {
+        let started_at = Instant::now();
+        let task = tokio::spawn(crate::bot::shard::main(
+            kernel.clone(),
+            commands.clone(),
+            extensions.clone(),
+            states.clone(),
+            shutdown.clone(),
+            shard,
+        ));
+
+        match task.await {
+            Ok(ShardExit::Shutdown) => break,
+            Ok(ShardExit::FatallyClosed(close_code)) if is_unrecoverable(close_code) => {
+                tracing::error!(%close_code, "Shard closed with an unrecoverable error");
+                kernel.shutdown(ShutdownReason::ShardFatalError(id));
+                break;
+            }
+            Ok(ShardExit::FatallyClosed(close_code)) => {
+                tracing::warn!(%close_code, "Shard has been fatally closed");
+            }
+            Err(error) => {
+                tracing::error!(?error, "Shard task has stopped unexpectedly");
+            }
+        }
+
+        if kernel.is_shutdown() {
             break;
         }
+
+        if started_at.elapsed() >= STABLE_RUN_DURATION {
+            consecutive_restarts = 0;
+        }
+
+        consecutive_restarts += 1;
+
+        let backoff = restart_backoff(consecutive_restarts);
+        let restarts = states.record_restart(id);
+        kernel.metrics().record_shard_restart(id);
+        tracing::warn!(
+            shard.restarts = %restarts,
+            "Restarting shard in {backoff:?} (attempt {consecutive_restarts})"
+        );
+
+        tokio::select! {
+            _ = kernel.shutdown_guard() => break,
+            _ = tokio::time::sleep(backoff) => {},
+        }
+    }
+}
+
+/// Copies the settings of `config` without the session it was
+/// resumed with or its resume URL, so a restarted shard identifies
+/// again through the default gateway URL.
+fn restart_config(config: &Config) -> Config {
+    let mut builder = Config::builder(config.token().to_owned(), config.intents())
+        .event_types(config.event_types())
+        .large_threshold(config.large_threshold())
+        .queue(config.queue().clone())
+        .ratelimit_messages(config.ratelimit_messages());
+
+    if let Some(properties) = config.identify_properties() {
+        builder = builder.identify_properties(properties.clone());
+    }
+    if let Some(presence) = config.presence() {
+        builder = builder.presence(presence.clone());
+    }
+
+    builder.build()
+}
+
+/// Close codes that will happen again no matter how many
+/// times the shard is restarted.
+fn is_unrecoverable(close_code: CloseCode) -> bool {
+    matches!(
+        close_code,
+        CloseCode::AuthenticationFailed
+            | CloseCode::InvalidShard
+            | CloseCode::ShardingRequired
+            | CloseCode::InvalidApiVersion
+            | CloseCode::InvalidIntents
+            | CloseCode::DisallowedIntents
+    )
+}
+
+fn restart_backoff(attempt: u32) -> Duration {
+    let backoff = Duration::from_secs(2u64.saturating_pow(attempt));
+    backoff.min(MAX_RESTART_BACKOFF)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_with_each_attempt() {
+        assert_eq!(restart_backoff(1), Duration::from_secs(2));
+        assert_eq!(restart_backoff(2), Duration::from_secs(4));
+        assert_eq!(restart_backoff(5), Duration::from_secs(32));
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        assert_eq!(restart_backoff(9), MAX_RESTART_BACKOFF);
+        assert_eq!(restart_backoff(u32::MAX), MAX_RESTART_BACKOFF);
+    }
+
+    #[test]
+    fn unrecoverable_close_codes() {
+        assert!(is_unrecoverable(CloseCode::AuthenticationFailed));
+        assert!(is_unrecoverable(CloseCode::DisallowedIntents));
+        assert!(is_unrecoverable(CloseCode::InvalidShard));
+        assert!(is_unrecoverable(CloseCode::ShardingRequired));
+        assert!(!is_unrecoverable(CloseCode::SessionTimedOut));
+        assert!(!is_unrecoverable(CloseCode::RateLimited));
     }
 }
@@ -0,0 +1,132 @@
+use derive_more::Display;
+use error_stack::{Result, ResultExt};
+use memobot_kernel::extension::PendingReloads;
+use memobot_kernel::{ExtensionRegistry, Kernel, ShutdownReason};
+use std::sync::Arc;
+
+use crate::util::tracing::{directives_from_env, LogFilter};
+use crate::util::ReloadSignal;
+
+#[derive(Debug, Display)]
+pub enum ReloadError {
+    /// Nothing has been applied, the previous configuration is still in use.
+    #[display(fmt = "Could not reload configuration")]
+    Rejected,
+    /// Some of the new configuration has been applied.
+    #[display(fmt = "Configuration was only partially reloaded")]
+    Partial,
+}
+impl error_stack::Context for ReloadError {}
+
+/// Reloads the configuration every time `SIGHUP` is received
+/// until the kernel is shutting down.
+pub async fn start(kernel: Kernel, extensions: Arc<ExtensionRegistry>, log_filter: LogFilter) {
+    let mut signal = ReloadSignal::new();
+    loop {
+        tokio::select! {
+            _ = kernel.shutdown_guard() => break,
+            _ = signal.recv() => {},
+        }
+
+        tracing::info!("Received reload signal, reloading configuration...");
+        // The bot keeps running with the previous configuration
+        // unless it cannot be rolled back to it anymore.
+        let Err(error) = reload(&kernel, &extensions, &log_filter) else {
+            continue;
+        };
+
+        tracing::error!(?error, "Failed to reload configuration");
+        if let ReloadError::Partial = error.current_context() {
+            kernel.shutdown(ShutdownReason::ConfigReloadFailed);
+        }
+    }
+}
+
+/// Re-reads the configuration file and applies every field that
+/// is safe to change while the bot is running.
+///
+/// Environment variables cannot change in a running process, so
+/// only changes made in the configuration file are picked up.
+///
+/// The configuration of the kernel, the log filter and every
+/// extension is validated first, nothing is applied if any of
+/// them is invalid.
+///
+/// Fails with [`ReloadError::Partial`] if the kernel's and the
+/// extensions' parts have been applied but the log filter could not be.
+#[tracing::instrument(skip_all)]
+pub fn reload(
+    kernel: &Kernel,
+    extensions: &ExtensionRegistry,
+    log_filter: &LogFilter,
+) -> Result<(), ReloadError> {
+    let snapshot = memobot_env_vars::snapshot_file();
+    let pending = match prepare(kernel, extensions) {
+        Ok(pending) => pending,
+        Err(error) => {
+            memobot_env_vars::restore_file(snapshot);
+            return Err(error);
+        }
+    };
+
+    let mut requires_restart = kernel
+        .reload_config(pending.config)
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+
+    requires_restart.extend(pending.extensions.apply());
+
+    // The kernel and extensions are already applied from here on
+    if pending.directives != log_filter.base() {
+        let changed = log_filter
+            .set_base(&pending.directives)
+            .change_context(ReloadError::Partial)?;
+
+        if changed {
+            tracing::info!("Log filter changed to {:?}", pending.directives);
+        } else {
+            tracing::info!(
+                "Log filter will change to {:?} once the temporary log filter expires",
+                pending.directives
+            );
+        }
+    }
+
+    if requires_restart.is_empty() {
+        tracing::info!("Configuration has been reloaded");
+    } else {
+        tracing::warn!(
+            "Configuration has been reloaded but these fields only take effect after a restart: {}",
+            requires_restart.join(", ")
+        );
+    }
+
+    Ok(())
+}
+
+/// Configuration that has been validated but not applied yet.
+struct PendingConfig {
+    config: memobot_kernel::Config,
+    directives: String,
+    extensions: PendingReloads,
+}
+
+fn prepare(kernel: &Kernel, extensions: &ExtensionRegistry) -> Result<PendingConfig, ReloadError> {
+    memobot_env_vars::load_file().change_context(ReloadError::Rejected)?;
+    crate::config::warn_unknown_keys();
+
+    let config = memobot_kernel::Config::from_env().change_context(ReloadError::Rejected)?;
+
+    let directives = directives_from_env();
+    LogFilter::validate(&directives).change_context(ReloadError::Rejected)?;
+
+    let extensions = extensions
+        .reload(kernel)
+        .change_context(ReloadError::Rejected)?;
+    Ok(PendingConfig {
+        config,
+        directives,
+        extensions,
+    })
+}
@@ -45,3 +45,48 @@ pub async fn shutdown_signal() {
         _ = sigterm.recv() => {},
     };
 }
+
+/// Yields every time the operating system asks the bot to
+/// reload its configuration.
+///
+/// **For Unix systems**: It detects whether `SIGHUP` is triggered
+///
+/// **For Windows / unsupported platforms**: It never yields
+#[cfg(unix)]
+pub struct ReloadSignal(tokio::signal::unix::Signal);
+
+#[cfg(unix)]
+impl Default for ReloadSignal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(unix)]
+impl ReloadSignal {
+    #[must_use]
+    pub fn new() -> Self {
+        use tokio::signal::unix::{signal, SignalKind};
+        Self(signal(SignalKind::hangup()).expect("failed to install SIGHUP handler"))
+    }
+
+    pub async fn recv(&mut self) {
+        self.0.recv().await;
+    }
+}
+
+#[cfg(not(unix))]
+#[derive(Default)]
+pub struct ReloadSignal;
+
+#[cfg(not(unix))]
+impl ReloadSignal {
+    #[must_use]
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub async fn recv(&mut self) {
+        std::future::pending::<()>().await;
+    }
+}
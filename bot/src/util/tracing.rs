@@ -1,22 +1,368 @@
+use derive_more::Display;
+use error_stack::{Report, Result, ResultExt};
+use memobot_env_vars::EnvVar;
+use memobot_kernel::{Environment, Kernel, Suggestion};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tracing::level_filters::LevelFilter;
-use tracing_subscriber::{filter::Targets, layer::SubscriberExt, util::SubscriberInitExt, Layer};
+use tracing_subscriber::{
+    filter::Targets, layer::SubscriberExt, reload, util::SubscriberInitExt, Layer, Registry,
+};
 
-pub fn init() {
-    let targets = dotenvy::var("RUST_LOG")
-        .unwrap_or_else(|_| "info".into())
+#[derive(Debug, Display)]
+#[display(fmt = "Could not change log filter")]
+pub struct LogFilterError;
+impl error_stack::Context for LogFilterError {}
+
+/// Handle to change the log filter while the bot is running.
+#[derive(Clone)]
+pub struct LogFilter {
+    handle: reload::Handle<Targets, Registry>,
+    state: Arc<Mutex<LogFilterState>>,
+}
+
+#[derive(Debug)]
+struct LogFilterState {
+    directives: String,
+    // Bumped on every change so a pending revert can tell
+    // whether the filter has been changed since.
+    generation: u64,
+    revert: Option<PendingRevert>,
+}
+
+impl LogFilterState {
+    fn base(&self) -> &str {
+        match &self.revert {
+            Some(revert) => &revert.directives,
+            None => &self.directives,
+        }
+    }
+}
+
+/// Scheduled change back to the previous log filter.
+#[derive(Debug, Clone)]
+pub struct PendingRevert {
+    pub directives: String,
+    pub at: Instant,
+}
+
+impl LogFilter {
+    /// Log filter directives currently in use.
+    #[must_use]
+    pub fn current(&self) -> String {
+        self.lock().directives.clone()
+    }
+
+    /// Log filter directives used once the temporary filter set
+    /// with [`LogFilter::set_for`] expires, or the current ones.
+    #[must_use]
+    pub fn base(&self) -> String {
+        self.lock().base().to_string()
+    }
+
+    /// Revert scheduled by [`LogFilter::set_for`], if any.
+    #[must_use]
+    pub fn pending_revert(&self) -> Option<PendingRevert> {
+        self.lock().revert.clone()
+    }
+
+    /// Replaces the log filter with new directives in the
+    /// same format as `RUST_LOG`.
+    pub fn set(&self, directives: &str) -> Result<(), LogFilterError> {
+        self.apply(directives, None).map(|_| ())
+    }
+
+    /// Same as [`LogFilter::set`] but a temporary filter set with
+    /// [`LogFilter::set_for`] is kept until it expires, `directives`
+    /// are used after that instead.
+    ///
+    /// Returns whether the log filter has changed right away.
+    pub fn set_base(&self, directives: &str) -> Result<bool, LogFilterError> {
+        let directives = directives.trim().trim_matches('"');
+        let targets = parse_directives(directives)?;
+
+        let mut state = self.lock();
+        if let Some(revert) = &mut state.revert {
+            revert.directives = directives.to_string();
+            return Ok(false);
+        }
+
+        self.handle.reload(targets).change_context(LogFilterError)?;
+        state.directives = directives.to_string();
+        state.generation += 1;
+        Ok(true)
+    }
+
+    /// Replaces the log filter and changes it back to the
+    /// [`LogFilter::base`] one after `duration`, unless it has
+    /// been changed since or the bot is shutting down.
+    ///
+    /// Useful to turn on debug logs of a noisy target for a while,
+    /// for example `twilight_gateway=debug` while chasing a disconnect.
+    pub fn set_for(
+        &self,
+        kernel: &Kernel,
+        directives: &str,
+        duration: Duration,
+    ) -> Result<(), LogFilterError> {
+        let at = Instant::now() + duration;
+        let generation = self.apply(directives, Some(at))?;
+
+        let filter = self.clone();
+        let kernel_1 = kernel.clone();
+        kernel.spawn(async move {
+            tokio::select! {
+                _ = kernel_1.shutdown_guard() => return,
+                _ = tokio::time::sleep_until(at.into()) => {},
+            }
+
+            // The directives to revert to may have been replaced
+            // by `set_base` in the meantime.
+            let directives = {
+                let state = filter.lock();
+                match &state.revert {
+                    Some(revert) if state.generation == generation => revert.directives.clone(),
+                    _ => return,
+                }
+            };
+
+            match filter.set(&directives) {
+                Ok(..) => tracing::info!("Log filter reverted to {directives:?}"),
+                Err(error) => tracing::warn!(?error, "Failed to revert log filter"),
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Checks whether `directives` can be used as a log filter.
+    pub fn validate(directives: &str) -> Result<(), LogFilterError> {
+        parse_directives(directives).map(|_| ())
+    }
+
+    /// Replaces the log filter, scheduling a revert to the base
+    /// directives at `revert_at` if it is set.
+    fn apply(&self, directives: &str, revert_at: Option<Instant>) -> Result<u64, LogFilterError> {
+        let directives = directives.trim().trim_matches('"');
+        let targets = parse_directives(directives)?;
+
+        let mut state = self.lock();
+        self.handle.reload(targets).change_context(LogFilterError)?;
+
+        // Taken under the same lock so a concurrent `set_base`
+        // cannot change the base in between.
+        let revert = revert_at.map(|at| PendingRevert {
+            directives: state.base().to_string(),
+            at,
+        });
+
+        state.directives = directives.to_string();
+        state.generation += 1;
+        state.revert = revert;
+        Ok(state.generation)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, LogFilterState> {
+        self.state.lock().expect("log filter lock is poisoned")
+    }
+}
+
+impl std::fmt::Debug for LogFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LogFilter")
+            .field("current", &self.current())
+            .finish()
+    }
+}
+
+fn parse_directives(directives: &str) -> Result<Targets, LogFilterError> {
+    let directives = directives.trim().trim_matches('"');
+    directives
+        .parse::<Targets>()
+        .change_context(LogFilterError)
+        .attach_printable_lazy(|| format!("invalid log filter {directives:?}"))
+}
+
+/// Reads log filter directives from `MEMOBOT_LOG` or `RUST_LOG`,
+/// either of them set in the environment wins over the file.
+#[must_use]
+pub fn directives_from_env() -> String {
+    let key = memobot_env_vars::resolve_key("MEMOBOT_LOG", &["RUST_LOG"]);
+    memobot_env_vars::var(key)
+        .ok()
+        .flatten()
+        .unwrap_or_else(|| "info".into())
         .trim()
         .trim_matches('"')
-        .parse::<Targets>()
-        .expect("Failed to parse `RUST_LOG` parameters");
+        .to_string()
+}
+
+/// How log lines are written to the standard output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Compact,
+    Json,
+    Pretty,
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "Could not parse log format")]
+pub struct LogFormatParseError;
+impl error_stack::Context for LogFormatParseError {}
+
+impl LogFormat {
+    /// Reads the log format from `MEMOBOT_LOG_FORMAT`, defaults to
+    /// pretty in development and JSON otherwise.
+    pub fn from_env() -> Result<Self, memobot_env_vars::ReadVarError> {
+        let format = memobot_env_vars::var_parsed::<LogFormat, _>("MEMOBOT_LOG_FORMAT")?;
+        if let Some(format) = format {
+            return Ok(format);
+        }
+
+        Environment::from_env().map(Self::default_for)
+    }
+
+    #[must_use]
+    pub fn default_for(environment: Environment) -> Self {
+        match environment {
+            Environment::Production => LogFormat::Json,
+            Environment::Development | Environment::Testing => LogFormat::Pretty,
+        }
+    }
+
+    fn layer(self) -> Box<dyn Layer<Registry> + Send + Sync> {
+        let layer = tracing_subscriber::fmt::layer();
+        match self {
+            LogFormat::Compact => layer.compact().boxed(),
+            LogFormat::Json => layer
+                .json()
+                .with_current_span(true)
+                .with_span_list(true)
+                .with_target(true)
+                .boxed(),
+            LogFormat::Pretty => layer.pretty().without_time().boxed(),
+        }
+    }
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = Report<LogFormatParseError>;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "compact" => Ok(Self::Compact),
+            "json" => Ok(Self::Json),
+            "pretty" => Ok(Self::Pretty),
+            _ => Err(Report::new(LogFormatParseError))
+                .attach(Suggestion::new(
+                    "choose log format only either 'pretty', 'compact' or 'json'",
+                ))
+                .attach_printable_lazy(|| format!("{s:?} could not be parsed")),
+        }
+    }
+}
 
-    let log_layer = tracing_subscriber::fmt::layer()
-        .pretty()
-        .without_time()
-        .with_filter(targets);
+/// Every variable read to set up logging.
+#[must_use]
+pub fn env_vars() -> Vec<EnvVar> {
+    vec![
+        EnvVar {
+            key: "MEMOBOT_LOG",
+            aliases: &["RUST_LOG"],
+            doc: "Log filter directives.",
+            default: Some("\"info\""),
+            default_value: Some(|| "info".into()),
+            enabled_by: None,
+            required: false,
+            sensitive: false,
+        },
+        EnvVar {
+            key: "MEMOBOT_LOG_FORMAT",
+            aliases: &[],
+            doc: "How log lines are written, either pretty, compact or json.",
+            default: Some("LogFormat::default_for(environment)"),
+            default_value: Some(|| {
+                let environment = Environment::from_env().unwrap_or(Environment::from_build());
+                format!("{:?}", LogFormat::default_for(environment))
+            }),
+            enabled_by: None,
+            required: false,
+            sensitive: false,
+        },
+    ]
+}
+
+/// Reads every logging variable and returns all problems found.
+#[must_use]
+pub fn check_env() -> Vec<Report<LogFilterError>> {
+    let mut problems = Vec::new();
+
+    let directives = directives_from_env();
+    if let Err(error) = parse_directives(&directives) {
+        problems.push(error);
+    }
+
+    if let Err(error) = LogFormat::from_env() {
+        problems.push(error.change_context(LogFilterError));
+    }
+
+    problems
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "Could not initialize logging")]
+pub struct InitLoggingError;
+impl error_stack::Context for InitLoggingError {}
+
+pub fn init() -> Result<LogFilter, InitLoggingError> {
+    let directives = directives_from_env();
+    let targets = parse_directives(&directives).change_context(InitLoggingError)?;
+
+    let format = LogFormat::from_env()
+        .change_context(InitLoggingError)
+        .attach_printable("failed to read MEMOBOT_LOG_FORMAT")?;
+
+    let (targets, handle) = reload::Layer::new(targets);
+    let log_layer = format.layer().with_filter(targets);
 
     let sentry_layer = sentry::integrations::tracing::layer().with_filter(LevelFilter::INFO);
     tracing_subscriber::registry()
         .with(log_layer)
         .with(sentry_layer)
         .init();
+
+    Ok(LogFilter {
+        handle,
+        state: Arc::new(Mutex::new(LogFilterState {
+            directives,
+            generation: 0,
+            revert: None,
+        })),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_format_from_str() {
+        assert_eq!("compact".parse::<LogFormat>().unwrap(), LogFormat::Compact);
+        assert_eq!("JSON".parse::<LogFormat>().unwrap(), LogFormat::Json);
+        assert_eq!("Pretty".parse::<LogFormat>().unwrap(), LogFormat::Pretty);
+        assert!("yaml".parse::<LogFormat>().is_err());
+        assert!("".parse::<LogFormat>().is_err());
+    }
+
+    #[test]
+    fn log_format_default_for_environment() {
+        assert_eq!(
+            LogFormat::default_for(Environment::Production),
+            LogFormat::Json
+        );
+        assert_eq!(
+            LogFormat::default_for(Environment::Development),
+            LogFormat::Pretty
+        );
+    }
 }
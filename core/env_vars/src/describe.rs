@@ -0,0 +1,30 @@
+/// Description of a variable read by a config type that
+/// derives [`FromEnv`](crate::FromEnv).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvVar {
+    pub key: &'static str,
+    /// Other names the variable can be set with, checked in
+    /// order after [`EnvVar::key`].
+    pub aliases: &'static [&'static str],
+    /// Documentation of the config field.
+    pub doc: &'static str,
+    /// Value used if the variable is not set, as written in the source.
+    pub default: Option<&'static str>,
+    /// Evaluates the default value and formats it.
+    pub default_value: Option<fn() -> String>,
+    /// Variable that enables the config type, this variable is
+    /// only read if that one is set.
+    pub enabled_by: Option<&'static str>,
+    pub required: bool,
+    pub sensitive: bool,
+}
+
+impl EnvVar {
+    /// Whether the variable or one of its aliases is set, either
+    /// from the environment or the configuration file.
+    pub fn is_set(&self) -> bool {
+        std::iter::once(self.key)
+            .chain(self.aliases.iter().copied())
+            .any(|key| matches!(crate::var(key), Ok(Some(..))))
+    }
+}
@@ -0,0 +1,332 @@
+use derive_more::Display;
+use error_stack::{Report, Result, ResultExt};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use toml_edit::{ImDocument, Item, Value};
+
+use crate::EnvVar;
+
+/// Configuration file that is used if `MEMOBOT_CONFIG` is not set.
+pub const DEFAULT_CONFIG_FILE: &str = "memobot.toml";
+
+/// Environment variable that points to the configuration file.
+pub const CONFIG_FILE_VAR: &str = "MEMOBOT_CONFIG";
+
+const KEY_PREFIX: &str = "MEMOBOT_";
+
+static CONFIG_FILE: RwLock<Option<ConfigFile>> = RwLock::new(None);
+
+#[derive(Debug, Display)]
+#[display(fmt = "Could not load configuration file {}", "_0.display()")]
+pub struct LoadFileError(PathBuf);
+impl error_stack::Context for LoadFileError {}
+
+/// Where a value of a configuration file is set.
+#[derive(Debug, Clone)]
+pub struct Origin {
+    pub path: PathBuf,
+    /// Dotted key of the value, for example `paradise.guild_id`.
+    pub key: String,
+    pub line: usize,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` at {}:{}", self.key, self.path.display(), self.line)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ConfigFile {
+    values: HashMap<String, (String, Origin)>,
+}
+
+/// Loads the configuration file at `MEMOBOT_CONFIG`, or
+/// `memobot.toml` in the current directory if it exists.
+///
+/// Every value in the file is available with the same
+/// functions as environment variables, named after its key.
+/// For example `port` in the `[api]` table can be read with
+/// `MEMOBOT_API_PORT`. Environment variables take precedence
+/// over values in the file. Arrays are read as comma-separated
+/// lists.
+///
+/// Returns the path of the loaded file, if any.
+pub fn load_file() -> Result<Option<PathBuf>, LoadFileError> {
+    let path = match dotenvy::var(CONFIG_FILE_VAR) {
+        Ok(path) => PathBuf::from(path),
+        Err(..) if Path::new(DEFAULT_CONFIG_FILE).exists() => PathBuf::from(DEFAULT_CONFIG_FILE),
+        Err(..) => {
+            set_file(None);
+            return Ok(None);
+        }
+    };
+
+    let file = read_file(&path)?;
+    set_file(Some(file));
+
+    Ok(Some(path))
+}
+
+/// Values of the configuration file currently in use, taken
+/// with [`snapshot_file`].
+#[derive(Debug)]
+pub struct FileSnapshot(Option<ConfigFile>);
+
+/// Takes the values of the configuration file currently in use so
+/// they can be put back with [`restore_file`], for example if a
+/// reload of the configuration has to be undone.
+#[must_use]
+pub fn snapshot_file() -> FileSnapshot {
+    FileSnapshot(
+        CONFIG_FILE
+            .read()
+            .expect("config file lock is poisoned")
+            .clone(),
+    )
+}
+
+/// Puts back the values taken with [`snapshot_file`].
+pub fn restore_file(snapshot: FileSnapshot) {
+    set_file(snapshot.0);
+}
+
+/// Values of the configuration file that are not read by any of
+/// `known` variables, which are most likely typos.
+#[must_use]
+pub fn unknown_file_keys(known: &[EnvVar]) -> Vec<Origin> {
+    let file = CONFIG_FILE.read().expect("config file lock is poisoned");
+    let Some(file) = file.as_ref() else {
+        return Vec::new();
+    };
+
+    let mut unknown = file
+        .values
+        .iter()
+        .filter(|(key, _)| {
+            !known
+                .iter()
+                .any(|v| v.key == key.as_str() || v.aliases.contains(&key.as_str()))
+        })
+        .map(|(_, (_, origin))| origin.clone())
+        .collect::<Vec<_>>();
+
+    unknown.sort_by_key(|v| v.line);
+    unknown
+}
+
+pub(crate) fn get(key: &str) -> Option<(String, Origin)> {
+    CONFIG_FILE
+        .read()
+        .expect("config file lock is poisoned")
+        .as_ref()
+        .and_then(|v| v.values.get(key).cloned())
+}
+
+fn set_file(file: Option<ConfigFile>) {
+    *CONFIG_FILE.write().expect("config file lock is poisoned") = file;
+}
+
+fn read_file(path: &Path) -> Result<ConfigFile, LoadFileError> {
+    let error = || LoadFileError(path.to_path_buf());
+    let content = std::fs::read_to_string(path).change_context_lazy(error)?;
+    let document = ImDocument::parse(content.as_str()).change_context_lazy(error)?;
+
+    let mut file = ConfigFile {
+        values: HashMap::new(),
+    };
+
+    let mut reader = Reader {
+        content: &content,
+        file: &mut file,
+        path,
+    };
+    reader.read(&[], document.as_item())?;
+
+    Ok(file)
+}
+
+struct Reader<'a> {
+    content: &'a str,
+    file: &'a mut ConfigFile,
+    path: &'a Path,
+}
+
+impl Reader<'_> {
+    fn read(&mut self, keys: &[&str], item: &Item) -> Result<(), LoadFileError> {
+        if let Some(table) = item.as_table_like() {
+            for (key, item) in table.iter() {
+                let mut keys = keys.to_vec();
+                keys.push(key);
+                self.read(&keys, item)?;
+            }
+            return Ok(());
+        }
+
+        let origin = Origin {
+            path: self.path.to_path_buf(),
+            key: keys.join("."),
+            line: self.line(item.span()),
+        };
+
+        let value = match item {
+            Item::Value(Value::Array(array)) => array
+                .iter()
+                .map(scalar)
+                .collect::<Option<Vec<_>>>()
+                .map(|v| v.join(",")),
+            Item::Value(value) => scalar(value),
+            _ => None,
+        };
+
+        let Some(value) = value else {
+            return Err(Report::new(LoadFileError(self.path.to_path_buf())))
+                .attach_printable(format!("unsupported value type for {origin}"));
+        };
+
+        let env_key = format!("{KEY_PREFIX}{}", keys.join("_").to_uppercase());
+        self.file.values.insert(env_key, (value, origin));
+
+        Ok(())
+    }
+
+    fn line(&self, span: Option<std::ops::Range<usize>>) -> usize {
+        span.and_then(|v| self.content.get(..v.start))
+            .map_or(0, |v| v.matches('\n').count() + 1)
+    }
+}
+
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(v) => Some(v.value().clone()),
+        Value::Integer(v) => Some(v.value().to_string()),
+        Value::Float(v) => Some(v.value().to_string()),
+        Value::Boolean(v) => Some(v.value().to_string()),
+        Value::Datetime(v) => Some(v.value().to_string()),
+        Value::Array(..) | Value::InlineTable(..) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{load_config, lock_env, set_vars, TempFile};
+    use crate::{var, var_with_source, Source};
+    use claims::{assert_none, assert_ok, assert_some, assert_some_eq};
+
+    const CONFIG: &str = r#"
+[file_test]
+port = 6500
+name = "memobot"
+ratio = 0.5
+enabled = true
+ids = [1, 2, 3]
+
+[file_test.nested]
+key = "value"
+"#;
+
+    #[test]
+    fn load_file_reads_every_value() {
+        let _lock = lock_env();
+        let _file = load_config(CONFIG);
+
+        assert_some_eq!(assert_ok!(var("MEMOBOT_FILE_TEST_PORT")), "6500");
+        assert_some_eq!(assert_ok!(var("MEMOBOT_FILE_TEST_NAME")), "memobot");
+        assert_some_eq!(assert_ok!(var("MEMOBOT_FILE_TEST_RATIO")), "0.5");
+        assert_some_eq!(assert_ok!(var("MEMOBOT_FILE_TEST_ENABLED")), "true");
+        assert_some_eq!(assert_ok!(var("MEMOBOT_FILE_TEST_IDS")), "1,2,3");
+        assert_some_eq!(assert_ok!(var("MEMOBOT_FILE_TEST_NESTED_KEY")), "value");
+        assert_none!(assert_ok!(var("MEMOBOT_FILE_TEST_MISSING")));
+    }
+
+    #[test]
+    fn load_file_keeps_origin() {
+        let _lock = lock_env();
+        let _file = load_config(CONFIG);
+
+        let (_, source) = assert_some!(assert_ok!(var_with_source("MEMOBOT_FILE_TEST_NAME")));
+        let Source::File(origin) = source else {
+            panic!("expected value from the file, got {source:?}");
+        };
+        assert_eq!(origin.key, "file_test.name");
+        assert_eq!(origin.line, 4);
+    }
+
+    #[test]
+    fn load_file_rejects_nested_arrays() {
+        let _lock = lock_env();
+        let file = TempFile::new("invalid.toml", "[file_test]\nids = [[1], [2]]\n");
+
+        let _vars = set_vars(
+            &[CONFIG_FILE_VAR],
+            &[(CONFIG_FILE_VAR, file.path().to_str().unwrap())],
+        );
+        assert!(load_file().is_err());
+    }
+
+    #[test]
+    fn env_wins_over_file() {
+        let _lock = lock_env();
+        let _file = load_config(CONFIG);
+
+        let _vars = set_vars(
+            &["MEMOBOT_FILE_TEST_PORT"],
+            &[("MEMOBOT_FILE_TEST_PORT", "7000")],
+        );
+        assert_some_eq!(assert_ok!(var("MEMOBOT_FILE_TEST_PORT")), "7000");
+    }
+
+    #[test]
+    fn alias_in_env_wins_over_key_in_file() {
+        let _lock = lock_env();
+        let _file = load_config(CONFIG);
+
+        assert_eq!(
+            crate::resolve_key("MEMOBOT_FILE_TEST_PORT", &["FILE_TEST_PORT"]),
+            "MEMOBOT_FILE_TEST_PORT"
+        );
+
+        let _vars = set_vars(&["FILE_TEST_PORT"], &[("FILE_TEST_PORT", "7000")]);
+        assert_eq!(
+            crate::resolve_key("MEMOBOT_FILE_TEST_PORT", &["FILE_TEST_PORT"]),
+            "FILE_TEST_PORT"
+        );
+    }
+
+    #[test]
+    fn unknown_keys_are_reported() {
+        let _lock = lock_env();
+        let _file = load_config("[file_test]\nport = 6500\nprot = 6501\n");
+
+        let known = [EnvVar {
+            key: "MEMOBOT_FILE_TEST_PORT",
+            aliases: &[],
+            doc: "",
+            default: None,
+            default_value: None,
+            enabled_by: None,
+            required: false,
+            sensitive: false,
+        }];
+
+        let unknown = unknown_file_keys(&known);
+        assert_eq!(unknown.len(), 1);
+        assert_eq!(unknown[0].key, "file_test.prot");
+    }
+
+    #[test]
+    fn restore_file_puts_back_snapshot() {
+        let _lock = lock_env();
+        let _file = load_config(CONFIG);
+
+        let snapshot = snapshot_file();
+        set_file(None);
+        assert_none!(assert_ok!(var("MEMOBOT_FILE_TEST_PORT")));
+
+        restore_file(snapshot);
+        assert_some_eq!(assert_ok!(var("MEMOBOT_FILE_TEST_PORT")), "6500");
+    }
+}
@@ -1,13 +1,31 @@
 // Copied from: https://github.com/rust-lang/crates.io/blob/main/crates_io_env_vars/src/lib.rs
 // License: MIT/Apache-2.0
 //
-// Testing is not included because the original source ties with anyhow but
+// Tests are adapted from the original source since it ties with anyhow but
 // we're using error-stack for error management.
 
 use derive_more::Display;
 use error_stack::{Context, Report, Result, ResultExt};
+use std::path::PathBuf;
 use std::str::FromStr;
 
+mod describe;
+mod file;
+#[cfg(test)]
+mod test_util;
+
+// Lets the tests use `#[derive(FromEnv)]`, which refers to this crate by name
+#[cfg(test)]
+extern crate self as memobot_env_vars;
+
+pub use describe::EnvVar;
+pub use memobot_env_vars_derive::FromEnv;
+
+pub use file::{
+    load_file, restore_file, snapshot_file, unknown_file_keys, FileSnapshot, LoadFileError, Origin,
+    CONFIG_FILE_VAR, DEFAULT_CONFIG_FILE,
+};
+
 #[derive(Debug, Display)]
 #[display(fmt = "Could not read {_0:?} environment variable")]
 pub struct ReadVarError(&'static str);
@@ -23,15 +41,104 @@ impl error_stack::Context for ReadVarError {}
 ///
 /// - [var] returns `Ok(None)` (instead of `Err`) if an environment variable
 ///   wasn't set.
+///
+/// - [var] reads the value from the file at `{key}_FILE` if the environment
+///   variable is not set, for secrets mounted as files.
+///
+/// - [var] falls back to the configuration file loaded with [load_file]
+///   if neither of them is set.
 #[track_caller]
 pub fn var(key: &'static str) -> Result<Option<String>, ReadVarError> {
-    match dotenvy::var(key) {
+    var_with_source(key).map(|v| v.map(|(content, _)| content))
+}
+
+/// Where the value of a variable is set.
+#[derive(Debug, Clone)]
+pub enum Source {
+    Env,
+    /// File at `{key}_FILE`.
+    SecretFile(PathBuf),
+    /// Configuration file loaded with [load_file].
+    File(Origin),
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Env => f.write_str("environment"),
+            Source::SecretFile(path) => write!(f, "secret file {}", path.display()),
+            Source::File(origin) => write!(f, "{origin}"),
+        }
+    }
+}
+
+/// Reads a variable in the same way as [var] along with
+/// where its value is set.
+#[track_caller]
+pub fn var_with_source(key: &'static str) -> Result<Option<(String, Source)>, ReadVarError> {
+    if let Some(content) = env_var(key, key)? {
+        return Ok(Some((content, Source::Env)));
+    }
+
+    if let Some((content, path)) = secret_file_var(key)? {
+        return Ok(Some((content, Source::SecretFile(path))));
+    }
+
+    Ok(file::get(key).map(|(content, origin)| (content, Source::File(origin))))
+}
+
+/// Picks which of `key` or its `aliases` should be read.
+///
+/// The environment takes precedence over the configuration file,
+/// so the first of them that is set in the environment (directly
+/// or through `{key}_FILE`) is picked before any of them is looked
+/// up in the file. Returns `key` if none of them is set.
+#[must_use]
+pub fn resolve_key(key: &'static str, aliases: &[&'static str]) -> &'static str {
+    let keys = || std::iter::once(key).chain(aliases.iter().copied());
+    let in_env = |key: &'static str| {
+        // Errors are left for the actual read to report
+        !matches!(env_var(key, key), Ok(None))
+            || !matches!(env_var(key, &format!("{key}_FILE")), Ok(None))
+    };
+
+    keys()
+        .find(|key| in_env(key))
+        .or_else(|| keys().find(|key| file::get(key).is_some()))
+        .unwrap_or(key)
+}
+
+fn env_var(key: &'static str, name: &str) -> Result<Option<String>, ReadVarError> {
+    match dotenvy::var(name) {
         Ok(content) => Ok(Some(content)),
         Err(dotenvy::Error::EnvVar(std::env::VarError::NotPresent)) => Ok(None),
         Err(error) => Err(error).change_context(ReadVarError(key)),
     }
 }
 
+/// Reads the variable from the file at `{key}_FILE`, in the
+/// same way as Docker secrets are mounted.
+fn secret_file_var(key: &'static str) -> Result<Option<(String, PathBuf)>, ReadVarError> {
+    let file_key = format!("{key}_FILE");
+    let Some(path) = env_var(key, &file_key)? else {
+        return Ok(None);
+    };
+
+    let content = std::fs::read_to_string(&path)
+        .change_context(ReadVarError(key))
+        .attach_printable_lazy(|| format!("could not read file {path:?} set by {file_key}"))?;
+
+    Ok(Some((content.trim().to_string(), PathBuf::from(path))))
+}
+
+/// Points at where the value is set in the configuration file.
+fn attach_origin<T>(result: Result<T, ReadVarError>, source: &Source) -> Result<T, ReadVarError> {
+    match source {
+        Source::File(origin) => result.attach_printable_lazy(|| format!("set by {origin}")),
+        Source::Env | Source::SecretFile(..) => result,
+    }
+}
+
 /// Reads an environment variable for the current process, and fails if it was
 /// not found.
 ///
@@ -61,14 +168,16 @@ where
     E: Context,
     R::Err: IntoReport<E> + Send + Sync + 'static,
 {
-    match var(key) {
-        Ok(Some(content)) => Ok(Some(
-            content
+    match var_with_source(key) {
+        Ok(Some((content, source))) => {
+            let value = content
                 .parse::<R>()
                 .map_err(|e| e.into_report())
                 .change_context(ReadVarError(key))
-                .attach_printable("couldn't parse environment variable")?,
-        )),
+                .attach_printable("couldn't parse environment variable");
+
+            attach_origin(value, &source).map(Some)
+        }
         Ok(None) => Ok(None),
         Err(error) => Err(error),
     }
@@ -91,6 +200,23 @@ where
     required(var_parsed(key), key)
 }
 
+// Used by code generated from `#[derive(FromEnv)]`
+#[doc(hidden)]
+pub mod __private {
+    use super::ReadVarError;
+    use error_stack::Result;
+
+    pub use super::resolve_key;
+    pub use error_stack;
+
+    pub fn required<T>(
+        res: Result<Option<T>, ReadVarError>,
+        key: &'static str,
+    ) -> Result<T, ReadVarError> {
+        super::required(res, key)
+    }
+}
+
 fn required<T>(res: Result<Option<T>, ReadVarError>, key: &'static str) -> Result<T, ReadVarError> {
     match res {
         // TODO: Find a good word/sentence for this
@@ -124,16 +250,19 @@ where
     E: IntoReport<C>,
     C: Context,
 {
-    let values = match var(key)? {
+    let values = match var_with_source(key)? {
         None => vec![],
-        Some(s) if s.is_empty() => vec![],
-        Some(s) => s
+        Some((s, _)) if s.is_empty() => vec![],
+        Some((s, source)) => s
             .split(',')
             .map(str::trim)
             .map(|s| {
-                f(s).map_err(|e| e.into_report())
+                let value = f(s)
+                    .map_err(|e| e.into_report())
                     .change_context(ReadVarError(key))
-                    .attach_printable_lazy(|| format!("failed to parse value \"{s}\""))
+                    .attach_printable_lazy(|| format!("failed to parse value \"{s}\""));
+
+                attach_origin(value, &source)
             })
             .collect::<Result<_, _>>()?,
     };
@@ -156,3 +285,285 @@ impl<T: Context> IntoReport<T> for Report<T> {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{lock_env, set_vars, TempFile};
+    use claims::{assert_err, assert_none, assert_ok, assert_ok_eq, assert_some, assert_some_eq};
+    use std::path::Path;
+
+    const TEST_VAR: &str = "MEMOBOT_ENV_VARS_TEST_VAR";
+    const TEST_VAR_FILE: &str = "MEMOBOT_ENV_VARS_TEST_VAR_FILE";
+    const KEYS: &[&str] = &[TEST_VAR, TEST_VAR_FILE];
+
+    #[test]
+    fn test_var() {
+        let _lock = lock_env();
+        let _vars = set_vars(KEYS, &[]);
+
+        std::env::set_var(TEST_VAR, "test");
+        assert_some_eq!(assert_ok!(var(TEST_VAR)), "test");
+
+        std::env::remove_var(TEST_VAR);
+        assert_none!(assert_ok!(var(TEST_VAR)));
+    }
+
+    #[test]
+    fn test_var_from_file() {
+        let _lock = lock_env();
+        let _vars = set_vars(KEYS, &[]);
+        let file = TempFile::new("secret", "secret\n");
+
+        std::env::set_var(TEST_VAR_FILE, file.path());
+        assert_some_eq!(assert_ok!(var(TEST_VAR)), "secret");
+
+        let (_, source) = assert_some!(assert_ok!(var_with_source(TEST_VAR)));
+        assert!(matches!(source, Source::SecretFile(path) if path == file.path()));
+    }
+
+    #[test]
+    fn test_var_wins_over_file() {
+        let _lock = lock_env();
+        let _vars = set_vars(KEYS, &[(TEST_VAR, "test")]);
+        let file = TempFile::new("secret", "secret");
+
+        std::env::set_var(TEST_VAR_FILE, file.path());
+        assert_some_eq!(assert_ok!(var(TEST_VAR)), "test");
+    }
+
+    #[test]
+    fn test_var_from_missing_file() {
+        let _lock = lock_env();
+        let _vars = set_vars(KEYS, &[]);
+
+        let path = Path::new("/nonexistent/memobot-env-vars-secret");
+        std::env::set_var(TEST_VAR_FILE, path);
+        let error = assert_err!(var(TEST_VAR));
+        assert!(format!("{error:?}").contains("could not read file"));
+    }
+
+    #[test]
+    fn test_resolve_key_from_file_alias() {
+        let _lock = lock_env();
+        let _vars = set_vars(KEYS, &[]);
+        let file = TempFile::new("secret", "secret");
+
+        let aliases = &["MEMOBOT_ENV_VARS_TEST_ALIAS", TEST_VAR];
+        assert_eq!(
+            resolve_key("MEMOBOT_ENV_VARS_TEST_KEY", aliases),
+            "MEMOBOT_ENV_VARS_TEST_KEY"
+        );
+
+        std::env::set_var(TEST_VAR_FILE, file.path());
+        assert_eq!(resolve_key("MEMOBOT_ENV_VARS_TEST_KEY", aliases), TEST_VAR);
+    }
+
+    #[test]
+    fn test_required_var() {
+        let _lock = lock_env();
+        let _vars = set_vars(KEYS, &[]);
+
+        std::env::set_var(TEST_VAR, "test");
+        assert_ok_eq!(required_var(TEST_VAR), "test");
+
+        std::env::remove_var(TEST_VAR);
+        let error = assert_err!(required_var(TEST_VAR));
+        assert!(format!("{error:?}").contains("environment variable is missing"));
+    }
+
+    #[test]
+    fn test_list() {
+        let _lock = lock_env();
+        let _vars = set_vars(KEYS, &[]);
+
+        std::env::set_var(TEST_VAR, "test");
+        assert_ok_eq!(list(TEST_VAR), vec!["test"]);
+
+        std::env::set_var(TEST_VAR, "test, foo,   bar   ");
+        assert_ok_eq!(list(TEST_VAR), vec!["test", "foo", "bar"]);
+
+        std::env::set_var(TEST_VAR, "");
+        assert_ok_eq!(list(TEST_VAR), Vec::<String>::new());
+
+        std::env::remove_var(TEST_VAR);
+        assert_ok_eq!(list(TEST_VAR), Vec::<String>::new());
+    }
+}
+
+#[cfg(test)]
+mod derive_tests {
+    use super::*;
+    use crate::test_util::{lock_env, set_vars};
+    use claims::{assert_none, assert_ok, assert_some};
+
+    #[derive(Debug, Display)]
+    #[display(fmt = "Could not load test config")]
+    struct TestConfigError;
+    impl error_stack::Context for TestConfigError {}
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct Sensitive<T>(T);
+
+    impl<T> From<T> for Sensitive<T> {
+        fn from(value: T) -> Self {
+            Self(value)
+        }
+    }
+
+    impl Sensitive<String> {
+        fn as_str(&self) -> &str {
+            &self.0
+        }
+    }
+
+    #[derive(Debug, FromEnv)]
+    #[env(error = TestConfigError)]
+    struct TestConfig {
+        /// Name of the
+        /// test config.
+        #[env(key = "MEMOBOT_DERIVE_TEST_NAME", alias = "DERIVE_TEST_NAME", required)]
+        name: String,
+        #[env(key = "MEMOBOT_DERIVE_TEST_PORT", default = 6500, copy)]
+        port: u16,
+        #[env(key = "MEMOBOT_DERIVE_TEST_TOKEN", sensitive)]
+        token: Option<Sensitive<String>>,
+        #[env(key = "MEMOBOT_DERIVE_TEST_WORDS", with = Self::words_from_env)]
+        words: Option<String>,
+        #[env(nested)]
+        feature: Option<FeatureConfig>,
+    }
+
+    impl TestConfig {
+        fn words_from_env(key: &'static str) -> Result<Option<String>, ReadVarError> {
+            var(key).map(|v| v.map(|v| v.to_uppercase()))
+        }
+    }
+
+    #[derive(Debug, FromEnv)]
+    #[env(error = TestConfigError)]
+    struct FeatureConfig {
+        #[env(key = "MEMOBOT_DERIVE_TEST_FEATURE_ID", enable, copy)]
+        id: u64,
+        #[env(key = "MEMOBOT_DERIVE_TEST_FEATURE_LIMIT", required, copy)]
+        limit: u32,
+    }
+
+    const KEYS: &[&str] = &[
+        "MEMOBOT_DERIVE_TEST_NAME",
+        "DERIVE_TEST_NAME",
+        "MEMOBOT_DERIVE_TEST_PORT",
+        "MEMOBOT_DERIVE_TEST_TOKEN",
+        "MEMOBOT_DERIVE_TEST_WORDS",
+        "MEMOBOT_DERIVE_TEST_FEATURE_ID",
+        "MEMOBOT_DERIVE_TEST_FEATURE_LIMIT",
+    ];
+
+    #[test]
+    fn test_from_env() {
+        let _lock = lock_env();
+        let _vars = set_vars(
+            KEYS,
+            &[
+                ("MEMOBOT_DERIVE_TEST_NAME", "memobot"),
+                ("MEMOBOT_DERIVE_TEST_TOKEN", "secret"),
+                ("MEMOBOT_DERIVE_TEST_WORDS", "hello"),
+            ],
+        );
+
+        let config = assert_ok!(TestConfig::from_env());
+        assert_eq!(config.name(), "memobot");
+        assert_eq!(config.port(), 6500);
+        assert_eq!(config.token(), Some("secret"));
+        assert_eq!(config.words(), Some("HELLO"));
+        assert_none!(config.feature());
+    }
+
+    #[test]
+    fn test_from_env_required() {
+        let _lock = lock_env();
+        let _vars = set_vars(KEYS, &[]);
+
+        assert!(TestConfig::from_env().is_err());
+    }
+
+    #[test]
+    fn test_from_env_alias() {
+        let _lock = lock_env();
+        let _vars = set_vars(KEYS, &[("DERIVE_TEST_NAME", "alias")]);
+
+        let config = assert_ok!(TestConfig::from_env());
+        assert_eq!(config.name(), "alias");
+    }
+
+    #[test]
+    fn test_from_env_nested() {
+        let _lock = lock_env();
+        let _vars = set_vars(
+            KEYS,
+            &[
+                ("MEMOBOT_DERIVE_TEST_NAME", "memobot"),
+                ("MEMOBOT_DERIVE_TEST_FEATURE_ID", "1"),
+                ("MEMOBOT_DERIVE_TEST_FEATURE_LIMIT", "10"),
+            ],
+        );
+
+        let config = assert_ok!(TestConfig::from_env());
+        let feature = assert_some!(config.feature());
+        assert_eq!(feature.id(), 1);
+        assert_eq!(feature.limit(), 10);
+    }
+
+    #[test]
+    fn test_disabled_config_is_not_read() {
+        let _lock = lock_env();
+        let _vars = set_vars(KEYS, &[("MEMOBOT_DERIVE_TEST_FEATURE_LIMIT", "invalid")]);
+
+        assert_none!(assert_ok!(FeatureConfig::from_env()));
+        assert!(FeatureConfig::check_env().is_empty());
+    }
+
+    #[test]
+    fn test_check_env_reports_every_problem() {
+        let _lock = lock_env();
+        let _vars = set_vars(
+            KEYS,
+            &[
+                ("MEMOBOT_DERIVE_TEST_PORT", "invalid"),
+                ("MEMOBOT_DERIVE_TEST_FEATURE_ID", "1"),
+            ],
+        );
+
+        // Missing name, invalid port and missing feature limit
+        assert_eq!(TestConfig::check_env().len(), 3);
+    }
+
+    #[test]
+    fn test_env_vars() {
+        let vars = TestConfig::env_vars();
+        let keys = vars.iter().map(|v| v.key).collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            [
+                "MEMOBOT_DERIVE_TEST_NAME",
+                "MEMOBOT_DERIVE_TEST_PORT",
+                "MEMOBOT_DERIVE_TEST_TOKEN",
+                "MEMOBOT_DERIVE_TEST_WORDS",
+                "MEMOBOT_DERIVE_TEST_FEATURE_ID",
+                "MEMOBOT_DERIVE_TEST_FEATURE_LIMIT",
+            ]
+        );
+
+        let name = &vars[0];
+        assert_eq!(name.aliases, ["DERIVE_TEST_NAME"]);
+        assert_eq!(name.doc, "Name of the test config.");
+        assert!(name.required);
+
+        let port = &vars[1];
+        assert_eq!(port.default, Some("6500"));
+        assert_eq!(port.default_value.map(|v| v()).as_deref(), Some("6500"));
+
+        assert!(vars[2].sensitive);
+        assert_eq!(vars[5].enabled_by, Some("MEMOBOT_DERIVE_TEST_FEATURE_ID"));
+    }
+}
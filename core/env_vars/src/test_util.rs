@@ -0,0 +1,88 @@
+//! Fixtures shared by the tests of this crate.
+
+use claims::assert_ok;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::{load_file, restore_file, snapshot_file, FileSnapshot, CONFIG_FILE_VAR};
+
+/// Tests change the environment and the loaded configuration file,
+/// which are shared by the whole process, so they run one at a time.
+pub(crate) fn lock_env() -> MutexGuard<'static, ()> {
+    static LOCK: Mutex<()> = Mutex::new(());
+    LOCK.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// File in the temporary directory that is removed once dropped.
+pub(crate) struct TempFile(PathBuf);
+
+impl TempFile {
+    pub(crate) fn new(name: &str, content: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("memobot-env-vars-{}-{name}", std::process::id()));
+        std::fs::write(&path, content).unwrap();
+        Self(path)
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Removes `keys` from the environment, then sets `vars`. Every
+/// key is removed again once the returned guard is dropped.
+pub(crate) fn set_vars(keys: &'static [&'static str], vars: &[(&str, &str)]) -> VarsGuard {
+    for key in keys {
+        std::env::remove_var(key);
+    }
+    for (key, value) in vars {
+        std::env::set_var(key, value);
+    }
+    VarsGuard(keys)
+}
+
+pub(crate) struct VarsGuard(&'static [&'static str]);
+
+impl Drop for VarsGuard {
+    fn drop(&mut self) {
+        for key in self.0 {
+            std::env::remove_var(key);
+        }
+    }
+}
+
+/// Loads `content` as the configuration file until the
+/// returned guard is dropped.
+pub(crate) fn load_config(content: &str) -> ConfigGuard {
+    let snapshot = snapshot_file();
+    let file = TempFile::new("config.toml", content);
+
+    std::env::set_var(CONFIG_FILE_VAR, file.path());
+    let loaded = load_file();
+    std::env::remove_var(CONFIG_FILE_VAR);
+
+    assert_eq!(assert_ok!(loaded).as_deref(), Some(file.path()));
+    ConfigGuard {
+        _file: file,
+        snapshot: Some(snapshot),
+    }
+}
+
+pub(crate) struct ConfigGuard {
+    _file: TempFile,
+    snapshot: Option<FileSnapshot>,
+}
+
+impl Drop for ConfigGuard {
+    fn drop(&mut self) {
+        if let Some(snapshot) = self.snapshot.take() {
+            restore_file(snapshot);
+        }
+    }
+}
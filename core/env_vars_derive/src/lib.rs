@@ -0,0 +1,567 @@
+//! Derive macro for config types that are loaded with `memobot_env_vars`.
+//!
+//! Use it through the re-export at `memobot_env_vars::FromEnv`.
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{Attribute, Data, DeriveInput, Expr, Fields, LitStr, Path, Type};
+
+/// Generates `from_env`, a getter for every field and `env_vars`,
+/// which describes every variable the type reads.
+///
+/// The error type of `from_env` is set with `#[env(error = MyError)]`
+/// on the struct, it must be a unit struct that implements
+/// `error_stack::Context`.
+///
+/// Every field is read from a variable with `#[env(...)]`:
+///
+/// - `key = "MEMOBOT_FOO"`: name of the variable.
+/// - `alias = "FOO"`: another name of the variable, checked after
+///   the key. It can be used multiple times. An alias set in the
+///   environment wins over the key set in the configuration file.
+/// - `default = expr`: value to use if the variable is not set.
+/// - `required`: fails if the variable is not set.
+/// - `enable`: `from_env` returns `Ok(None)` if the variable is not set.
+///   Only one field can have it.
+/// - `sensitive`: the field is a `Sensitive<T>`, `T` is read instead.
+/// - `with = path`: reads the variable with a function that has the
+///   same signature as `memobot_env_vars::var`, returning the inner type.
+/// - `nested`: the field is another config type that derives `FromEnv`.
+/// - `copy`: the getter returns the field by value.
+///
+/// Fields with an `Option` type are left as `None` if the variable
+/// is not set, the others need either `default`, `required` or `enable`.
+#[proc_macro_derive(FromEnv, attributes(env))]
+pub fn derive_from_env(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[derive(Default)]
+struct FieldAttrs {
+    key: Option<LitStr>,
+    aliases: Vec<LitStr>,
+    default: Option<Expr>,
+    required: bool,
+    enable: bool,
+    sensitive: bool,
+    with: Option<Path>,
+    nested: bool,
+    copy: bool,
+}
+
+struct Field<'a> {
+    ident: &'a syn::Ident,
+    ty: &'a Type,
+    docs: Vec<&'a Attribute>,
+    attrs: FieldAttrs,
+}
+
+impl Field<'_> {
+    fn option_inner(&self) -> Option<&Type> {
+        generic_inner(self.ty, "Option")
+    }
+
+    /// Type without `Option`.
+    fn value_ty(&self) -> &Type {
+        self.option_inner().unwrap_or(self.ty)
+    }
+
+    /// Type that is read from the variable.
+    fn read_ty(&self) -> &Type {
+        let ty = self.value_ty();
+        if self.attrs.sensitive {
+            generic_inner(ty, "Sensitive").unwrap_or(ty)
+        } else {
+            ty
+        }
+    }
+
+    fn key(&self) -> &LitStr {
+        self.attrs
+            .key
+            .as_ref()
+            .expect("checked by parse_field_attrs")
+    }
+
+    fn doc(&self) -> String {
+        let lines = self
+            .docs
+            .iter()
+            .filter_map(|attr| match &attr.meta {
+                syn::Meta::NameValue(syn::MetaNameValue {
+                    value:
+                        Expr::Lit(syn::ExprLit {
+                            lit: syn::Lit::Str(s),
+                            ..
+                        }),
+                    ..
+                }) => Some(s.value().trim().to_string()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        lines.join(" ")
+    }
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new(
+            input.span(),
+            "FromEnv can only be derived for structs",
+        ));
+    };
+
+    let Fields::Named(named) = &data.fields else {
+        return Err(syn::Error::new(
+            input.span(),
+            "FromEnv can only be derived for structs with named fields",
+        ));
+    };
+
+    let error = parse_struct_attrs(input)?;
+    let fields = named
+        .named
+        .iter()
+        .map(|field| {
+            Ok(Field {
+                ident: field.ident.as_ref().expect("named field"),
+                ty: &field.ty,
+                docs: field
+                    .attrs
+                    .iter()
+                    .filter(|v| v.path().is_ident("doc"))
+                    .collect(),
+                attrs: parse_field_attrs(field)?,
+            })
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let enable = {
+        let mut enable = fields.iter().filter(|v| v.attrs.enable);
+        let first = enable.next();
+        if let Some(second) = enable.next() {
+            return Err(syn::Error::new(
+                second.ident.span(),
+                "only one field can have `enable`",
+            ));
+        }
+        first
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    // The enabling variable is read first so a disabled config
+    // does not fail because of its other variables.
+    let ordered = enable
+        .into_iter()
+        .chain(fields.iter().filter(|v| !v.attrs.enable))
+        .collect::<Vec<_>>();
+
+    let loaders = ordered.iter().map(|field| loader(field, &error));
+    let checkers = ordered.iter().map(|field| checker(field));
+
+    let idents = fields.iter().map(|v| v.ident);
+    let (output, result) = if enable.is_some() {
+        (
+            quote!(::std::option::Option<Self>),
+            quote!(::std::option::Option::Some(Self { #(#idents),* })),
+        )
+    } else {
+        (quote!(Self), quote!(Self { #(#idents),* }))
+    };
+
+    let enabled_by = match enable.and_then(|v| v.attrs.key.as_ref()) {
+        Some(key) => quote!(::std::option::Option::Some(#key)),
+        None => quote!(::std::option::Option::None),
+    };
+
+    let descriptions = fields.iter().map(|field| describe(field, &enabled_by));
+    let getters = fields.iter().map(getter);
+
+    Ok(quote! {
+        impl #impl_generics #name #ty_generics #where_clause {
+            pub fn from_env() -> ::memobot_env_vars::__private::error_stack::Result<#output, #error> {
+                #(#loaders)*
+                ::std::result::Result::Ok(#result)
+            }
+
+            /// Reads every variable like [`Self::from_env`] but returns
+            /// all problems found instead of stopping at the first one.
+            #[must_use]
+            pub fn check_env() -> ::std::vec::Vec<
+                ::memobot_env_vars::__private::error_stack::Report<::memobot_env_vars::ReadVarError>,
+            > {
+                let mut problems = ::std::vec::Vec::new();
+                #(#checkers)*
+                problems
+            }
+
+            /// Every variable read by [`Self::from_env`].
+            #[must_use]
+            pub fn env_vars() -> ::std::vec::Vec<::memobot_env_vars::EnvVar> {
+                let mut vars = ::std::vec::Vec::new();
+                #(#descriptions)*
+                vars
+            }
+        }
+
+        impl #impl_generics #name #ty_generics #where_clause {
+            #(#getters)*
+        }
+    })
+}
+
+fn parse_struct_attrs(input: &DeriveInput) -> syn::Result<Path> {
+    let mut error = None;
+    for attr in input.attrs.iter().filter(|v| v.path().is_ident("env")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("error") {
+                error = Some(meta.value()?.parse::<Path>()?);
+                Ok(())
+            } else {
+                Err(meta.error("unknown attribute"))
+            }
+        })?;
+    }
+
+    error.ok_or_else(|| {
+        syn::Error::new(
+            input.ident.span(),
+            "missing error type, add #[env(error = MyError)] to the struct",
+        )
+    })
+}
+
+fn parse_field_attrs(field: &syn::Field) -> syn::Result<FieldAttrs> {
+    let mut attrs = FieldAttrs::default();
+    for attr in field.attrs.iter().filter(|v| v.path().is_ident("env")) {
+        attr.parse_nested_meta(|meta| {
+            let path = &meta.path;
+            if path.is_ident("key") {
+                attrs.key = Some(meta.value()?.parse()?);
+            } else if path.is_ident("alias") {
+                attrs.aliases.push(meta.value()?.parse()?);
+            } else if path.is_ident("default") {
+                attrs.default = Some(meta.value()?.parse()?);
+            } else if path.is_ident("with") {
+                attrs.with = Some(meta.value()?.parse()?);
+            } else if path.is_ident("required") {
+                attrs.required = true;
+            } else if path.is_ident("enable") {
+                attrs.enable = true;
+            } else if path.is_ident("sensitive") {
+                attrs.sensitive = true;
+            } else if path.is_ident("nested") {
+                attrs.nested = true;
+            } else if path.is_ident("copy") {
+                attrs.copy = true;
+            } else {
+                return Err(meta.error("unknown attribute"));
+            }
+            Ok(())
+        })?;
+    }
+
+    let span = field.span();
+    if attrs.nested {
+        if attrs.key.is_some() || attrs.default.is_some() || attrs.required || attrs.enable {
+            return Err(syn::Error::new(
+                span,
+                "nested fields cannot have `key`, `default`, `required` or `enable`",
+            ));
+        }
+        return Ok(attrs);
+    }
+
+    if attrs.key.is_none() {
+        return Err(syn::Error::new(
+            span,
+            "missing variable name, add #[env(key = \"MEMOBOT_...\")]",
+        ));
+    }
+
+    let is_option = generic_inner(&field.ty, "Option").is_some();
+    let modes = [
+        is_option,
+        attrs.default.is_some(),
+        attrs.required,
+        attrs.enable,
+    ];
+    match modes.iter().filter(|v| **v).count() {
+        1 => {}
+        0 => {
+            return Err(syn::Error::new(
+                span,
+                "field must be an `Option` or have either `default`, `required` or `enable`",
+            ))
+        }
+        _ => {
+            return Err(syn::Error::new(
+                span,
+                "`Option` fields, `default`, `required` and `enable` cannot be combined",
+            ))
+        }
+    }
+
+    if attrs.sensitive {
+        let ty = generic_inner(&field.ty, "Option").unwrap_or(&field.ty);
+        if generic_inner(ty, "Sensitive").is_none() {
+            return Err(syn::Error::new(
+                span,
+                "`sensitive` fields must be a `Sensitive<T>`",
+            ));
+        }
+    }
+
+    Ok(attrs)
+}
+
+fn loader(field: &Field<'_>, error: &Path) -> TokenStream2 {
+    let ident = field.ident;
+    let change_context =
+        quote!(::memobot_env_vars::__private::error_stack::ResultExt::change_context);
+
+    if field.attrs.nested {
+        let ty = field.value_ty();
+        return quote! {
+            let #ident = #change_context(<#ty>::from_env(), #error)?;
+        };
+    }
+
+    let key = field.key();
+    let read = read(field);
+
+    let wrap = if field.attrs.sensitive {
+        quote!(::std::convert::From::from)
+    } else {
+        quote!(::std::convert::identity)
+    };
+
+    let value = if field.attrs.required {
+        quote! {
+            #wrap(#change_context(
+                ::memobot_env_vars::__private::required(value, #key),
+                #error,
+            )?)
+        }
+    } else if field.attrs.enable {
+        quote! {
+            match #change_context(value, #error)? {
+                ::std::option::Option::Some(value) => #wrap(value),
+                ::std::option::Option::None => return ::std::result::Result::Ok(::std::option::Option::None),
+            }
+        }
+    } else if let Some(default) = &field.attrs.default {
+        quote! {
+            #wrap(#change_context(value, #error)?.unwrap_or_else(|| #default))
+        }
+    } else {
+        quote! {
+            #change_context(value, #error)?.map(#wrap)
+        }
+    };
+
+    quote! {
+        let #ident = {
+            let value = #read;
+            #value
+        };
+    }
+}
+
+/// Reads the field's variable, or one of its aliases if it is not
+/// set, as a `Result<Option<T>, ReadVarError>`.
+///
+/// Aliases set in the environment win over the field's variable
+/// set in the configuration file, see `resolve_key`.
+fn read(field: &Field<'_>) -> TokenStream2 {
+    let read_ty = field.read_ty();
+    let key = field.key();
+    let aliases = &field.attrs.aliases;
+
+    let read = match &field.attrs.with {
+        Some(with) => quote!(#with(key)),
+        None if is_ident(read_ty, "String") => quote!(::memobot_env_vars::var(key)),
+        None => quote!(::memobot_env_vars::var_parsed::<#read_ty, _>(key)),
+    };
+
+    quote! {{
+        let key = ::memobot_env_vars::__private::resolve_key(#key, &[#(#aliases),*]);
+        let value: ::memobot_env_vars::__private::error_stack::Result<
+            ::std::option::Option<#read_ty>,
+            ::memobot_env_vars::ReadVarError,
+        > = #read;
+        value
+    }}
+}
+
+fn checker(field: &Field<'_>) -> TokenStream2 {
+    if field.attrs.nested {
+        let ty = field.value_ty();
+        return quote!(problems.extend(<#ty>::check_env()););
+    }
+
+    let key = field.key();
+    let read = read(field);
+    let missing = if field.attrs.enable {
+        quote!(return problems)
+    } else if field.attrs.required {
+        quote! {
+            if let ::std::result::Result::Err(error) =
+                ::memobot_env_vars::__private::required::<()>(::std::result::Result::Ok(None), #key)
+            {
+                problems.push(error);
+            }
+        }
+    } else {
+        quote!({})
+    };
+
+    quote! {
+        match #read {
+            ::std::result::Result::Ok(::std::option::Option::Some(..)) => {}
+            ::std::result::Result::Ok(::std::option::Option::None) => #missing,
+            ::std::result::Result::Err(error) => problems.push(error),
+        }
+    }
+}
+
+fn describe(field: &Field<'_>, enabled_by: &TokenStream2) -> TokenStream2 {
+    if field.attrs.nested {
+        let ty = field.value_ty();
+        return quote!(vars.extend(<#ty>::env_vars()););
+    }
+
+    let key = field.key();
+    let aliases = &field.attrs.aliases;
+    let doc = field.doc();
+    let (default, default_value) = match &field.attrs.default {
+        Some(default) => {
+            let text = quote!(#default).to_string();
+            let value = if is_ident(field.read_ty(), "String") {
+                quote!(|| ::std::string::ToString::to_string(&#default))
+            } else {
+                quote!(|| ::std::format!("{:?}", #default))
+            };
+            (
+                quote!(::std::option::Option::Some(#text)),
+                quote!(::std::option::Option::Some(#value)),
+            )
+        }
+        None => (
+            quote!(::std::option::Option::None),
+            quote!(::std::option::Option::None),
+        ),
+    };
+
+    let enabled_by = if field.attrs.enable {
+        quote!(::std::option::Option::None)
+    } else {
+        enabled_by.clone()
+    };
+
+    let required = field.attrs.required;
+    let sensitive = field.attrs.sensitive;
+
+    quote! {
+        vars.push(::memobot_env_vars::EnvVar {
+            key: #key,
+            aliases: &[#(#aliases),*],
+            doc: #doc,
+            default: #default,
+            default_value: #default_value,
+            enabled_by: #enabled_by,
+            required: #required,
+            sensitive: #sensitive,
+        });
+    }
+}
+
+fn getter(field: &Field<'_>) -> TokenStream2 {
+    let ident = field.ident;
+    let docs = &field.docs;
+    let ty = field.ty;
+    let is_option = field.option_inner().is_some();
+    let read_ty = field.read_ty();
+    let is_string = !field.attrs.nested && is_ident(read_ty, "String");
+
+    let (output, body) = if field.attrs.copy {
+        (quote!(#ty), quote!(self.#ident))
+    } else if field.attrs.sensitive && is_string {
+        if is_option {
+            (
+                quote!(::std::option::Option<&str>),
+                quote!(self.#ident.as_ref().map(|v| v.as_str())),
+            )
+        } else {
+            (quote!(&str), quote!(self.#ident.as_str()))
+        }
+    } else if field.attrs.sensitive {
+        if is_option {
+            (
+                quote!(::std::option::Option<&#read_ty>),
+                quote!(self.#ident.as_ref().map(|v| ::std::convert::AsRef::<#read_ty>::as_ref(v))),
+            )
+        } else {
+            (
+                quote!(&#read_ty),
+                quote!(::std::convert::AsRef::<#read_ty>::as_ref(&self.#ident)),
+            )
+        }
+    } else if is_string {
+        if is_option {
+            (
+                quote!(::std::option::Option<&str>),
+                quote!(self.#ident.as_deref()),
+            )
+        } else {
+            (quote!(&str), quote!(&self.#ident))
+        }
+    } else if is_option {
+        (
+            quote!(::std::option::Option<&#read_ty>),
+            quote!(self.#ident.as_ref()),
+        )
+    } else {
+        (quote!(&#ty), quote!(&self.#ident))
+    };
+
+    quote! {
+        #(#docs)*
+        #[must_use]
+        pub fn #ident(&self) -> #output {
+            #body
+        }
+    }
+}
+
+/// Returns `T` if the type is `{name}<T>`.
+fn generic_inner<'a>(ty: &'a Type, name: &str) -> Option<&'a Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+
+    let segment = path.path.segments.last()?;
+    if segment.ident != name {
+        return None;
+    }
+
+    let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+
+    match args.args.first()? {
+        syn::GenericArgument::Type(ty) => Some(ty),
+        _ => None,
+    }
+}
+
+fn is_ident(ty: &Type, name: &str) -> bool {
+    matches!(ty, Type::Path(path) if path.path.is_ident(name))
+}
@@ -1,4 +1,7 @@
 fn main() {
+    // `sqlx::migrate!` embeds migrations at compile time
+    println!("cargo:rerun-if-changed=migrations");
+
     if let Ok("release") = std::env::var("PROFILE").as_deref() {
         println!("cargo:rustc-cfg=release");
     }
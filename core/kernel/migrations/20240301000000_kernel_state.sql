@@ -0,0 +1,7 @@
+-- Small key-value store for state that the kernel needs
+-- to keep across restarts.
+CREATE TABLE IF NOT EXISTS kernel_state (
+    key TEXT PRIMARY KEY NOT NULL,
+    value TEXT NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,10 @@
+-- Gateway sessions saved during graceful shutdown so
+-- shards can resume them on the next boot.
+CREATE TABLE IF NOT EXISTS gateway_sessions (
+    shard_id INTEGER PRIMARY KEY NOT NULL,
+    shard_total INTEGER NOT NULL,
+    session_id TEXT NOT NULL,
+    sequence INTEGER NOT NULL,
+    resume_url TEXT NOT NULL,
+    saved_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,18 @@
+use actix_web::{http::header, HttpRequest};
+use constant_time_eq::constant_time_eq;
+
+/// Checks if the request has `expected` as its bearer token.
+///
+/// The comparison is timing-safe so the token cannot be guessed
+/// from how long a rejected request took.
+#[must_use]
+pub fn has_bearer_token(req: &HttpRequest, expected: &str) -> bool {
+    let token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .unwrap_or_default();
+
+    constant_time_eq(expected.as_bytes(), token.as_bytes())
+}
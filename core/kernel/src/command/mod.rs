@@ -0,0 +1,29 @@
+use async_trait::async_trait;
+use derive_more::Display;
+use error_stack::Result;
+use twilight_interactions::command::{CommandModel, CreateCommand};
+use twilight_model::application::interaction::Interaction;
+
+use crate::Context;
+
+mod registry;
+
+pub use registry::{CommandRegistry, CommandScope};
+
+/// A slash command that can be registered in a [`CommandRegistry`].
+///
+/// Its name, description and options are declared with
+/// [`twilight-interactions`](twilight_interactions) derive macros
+/// and its options are parsed from the interaction before [`run`]
+/// is called.
+///
+/// [`run`]: Command::run
+#[async_trait]
+pub trait Command: CommandModel + CreateCommand + Send + 'static {
+    async fn run(self, ctx: &Context, interaction: &Interaction) -> Result<(), CommandError>;
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "Failed to run command")]
+pub struct CommandError;
+impl error_stack::Context for CommandError {}
@@ -0,0 +1,225 @@
+use error_stack::{FutureExt, Result, ResultExt};
+use futures::future::BoxFuture;
+use std::collections::HashMap;
+use std::future::IntoFuture;
+use std::sync::atomic::{AtomicBool, Ordering};
+use twilight_interactions::command::CommandInputData;
+use twilight_model::application::command::Command as CommandDefinition;
+use twilight_model::application::interaction::application_command::CommandData;
+use twilight_model::application::interaction::{Interaction, InteractionData};
+use twilight_model::channel::message::MessageFlags;
+use twilight_model::http::interaction::{InteractionResponse, InteractionResponseType};
+use twilight_model::id::{marker::GuildMarker, Id};
+use twilight_util::builder::InteractionResponseDataBuilder;
+
+use super::{Command, CommandError};
+use crate::Context;
+
+type Handler =
+    fn(Context, Interaction, CommandData) -> BoxFuture<'static, Result<(), CommandError>>;
+
+struct RegisteredCommand {
+    definition: CommandDefinition,
+    handler: Handler,
+    scope: CommandScope,
+}
+
+/// Where a command is registered on Discord.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CommandScope {
+    Global,
+    Guild(Id<GuildMarker>),
+}
+
+impl std::fmt::Display for CommandScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Global => f.write_str("global"),
+            Self::Guild(id) => write!(f, "guild ({id})"),
+        }
+    }
+}
+
+/// Keeps track of every slash command the bot knows about and
+/// routes incoming interactions to their handlers.
+pub struct CommandRegistry {
+    commands: HashMap<String, RegisteredCommand>,
+    synced: AtomicBool,
+}
+
+impl CommandRegistry {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            commands: HashMap::new(),
+            synced: AtomicBool::new(false),
+        }
+    }
+
+    /// Registers a global command, replacing any previously
+    /// registered command that has the same name.
+    pub fn register<C: Command>(&mut self) {
+        self.register_with_scope::<C>(CommandScope::Global);
+    }
+
+    /// Registers a command that is only available in a specific guild,
+    /// replacing any previously registered command that has the same name.
+    pub fn register_in_guild<C: Command>(&mut self, guild_id: Id<GuildMarker>) {
+        self.register_with_scope::<C>(CommandScope::Guild(guild_id));
+    }
+
+    fn register_with_scope<C: Command>(&mut self, scope: CommandScope) {
+        let definition: CommandDefinition = C::create_command().into();
+        if self.commands.contains_key(&definition.name) {
+            tracing::warn!("Command {:?} is registered more than once", definition.name);
+        }
+
+        let handler: Handler = |ctx, interaction, data| {
+            Box::pin(async move {
+                let command = C::from_interaction(CommandInputData::from(data))
+                    .change_context(CommandError)
+                    .attach_printable("could not parse command options")?;
+
+                command.run(&ctx, &interaction).await
+            })
+        };
+
+        self.commands.insert(
+            definition.name.clone(),
+            RegisteredCommand {
+                definition,
+                handler,
+                scope,
+            },
+        );
+    }
+}
+
+impl CommandRegistry {
+    /// Definitions of all registered commands (name, description and options).
+    pub fn definitions(&self) -> impl Iterator<Item = &CommandDefinition> {
+        self.commands.values().map(|v| &v.definition)
+    }
+
+    /// Definitions of all registered commands under a specific scope.
+    pub fn definitions_with_scope(
+        &self,
+        scope: CommandScope,
+    ) -> impl Iterator<Item = &CommandDefinition> {
+        self.commands
+            .values()
+            .filter(move |v| v.scope == scope)
+            .map(|v| &v.definition)
+    }
+
+    /// All scopes that have at least one registered command.
+    pub fn scopes(&self) -> impl Iterator<Item = CommandScope> + '_ {
+        let mut scopes = Vec::new();
+        for command in self.commands.values() {
+            if !scopes.contains(&command.scope) {
+                scopes.push(command.scope);
+            }
+        }
+        scopes.into_iter()
+    }
+
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&CommandDefinition> {
+        self.commands.get(name).map(|v| &v.definition)
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Marks the commands as synchronized with Discord.
+    ///
+    /// Returns `false` if they already were, so only one caller
+    /// synchronizes them. Call [`CommandRegistry::unmark_synced`]
+    /// if synchronizing fails so it can be retried.
+    pub fn mark_synced(&self) -> bool {
+        !self.synced.swap(true, Ordering::SeqCst)
+    }
+
+    pub fn unmark_synced(&self) {
+        self.synced.store(false, Ordering::SeqCst);
+    }
+}
+
+impl CommandRegistry {
+    #[tracing::instrument(skip_all, fields(
+        interaction.id = %interaction.id,
+        interaction.kind = ?interaction.kind,
+    ))]
+    pub async fn dispatch(&self, ctx: Context, mut interaction: Interaction) {
+        let Some(InteractionData::ApplicationCommand(data)) = interaction.data.take() else {
+            tracing::debug!("Ignoring non-command interaction");
+            return;
+        };
+
+        let Some(command) = self.commands.get(&data.name) else {
+            tracing::warn!("Received unknown command {:?}", data.name);
+            return;
+        };
+
+        tracing::debug!("Running command {:?}", data.name);
+        let name = data.name.clone();
+        let result = (command.handler)(ctx.clone(), interaction.clone(), *data).await;
+
+        if let Err(error) = result {
+            tracing::error!(?error, "Failed to run command {name:?}");
+            if let Err(error) = Self::respond_with_error(&ctx, &interaction).await {
+                tracing::warn!(
+                    ?error,
+                    "Could not tell the user that command {name:?} failed"
+                );
+            }
+        }
+    }
+
+    async fn respond_with_error(
+        ctx: &Context,
+        interaction: &Interaction,
+    ) -> Result<(), CommandError> {
+        let data = InteractionResponseDataBuilder::new()
+            .content("Something went wrong while running this command.")
+            .flags(MessageFlags::EPHEMERAL)
+            .build();
+
+        let response = InteractionResponse {
+            kind: InteractionResponseType::ChannelMessageWithSource,
+            data: Some(data),
+        };
+
+        ctx.kernel()
+            .interaction()
+            .await
+            .create_response(interaction.id, &interaction.token, &response)
+            .into_future()
+            .change_context(CommandError)
+            .await?;
+
+        Ok(())
+    }
+}
+
+impl Default for CommandRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for CommandRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CommandRegistry")
+            .field("commands", &self.commands.keys().collect::<Vec<_>>())
+            .field("synced", &self.synced.load(Ordering::SeqCst))
+            .finish()
+    }
+}
@@ -1,10 +1,20 @@
 use derive_more::Display;
-use error_stack::{Result, ResultExt};
+use error_stack::Result;
+use memobot_env_vars::FromEnv;
 use std::net::{IpAddr, Ipv4Addr};
 
-#[derive(Debug)]
+use crate::Sensitive;
+
+#[derive(Debug, Clone, PartialEq, Eq, FromEnv)]
+#[env(error = ApiConfigLoadError)]
 pub struct ApiConfig {
+    #[env(key = "MEMOBOT_API_ADDRESS", default = IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), copy)]
     address: IpAddr,
+    /// Bearer token required to access the admin API, the
+    /// admin API is disabled if it is not set.
+    #[env(key = "MEMOBOT_API_ADMIN_TOKEN", sensitive, with = Self::admin_token_from_env)]
+    admin_token: Option<Sensitive<String>>,
+    #[env(key = "MEMOBOT_API_PORT", default = 6500, copy)]
     port: u16,
 }
 
@@ -14,27 +24,10 @@ pub struct ApiConfigLoadError;
 impl error_stack::Context for ApiConfigLoadError {}
 
 impl ApiConfig {
-    pub fn from_env() -> Result<Self, ApiConfigLoadError> {
-        let address = memobot_env_vars::var_parsed("MEMOBOT_API_ADDRESS")
-            .change_context(ApiConfigLoadError)?
-            .unwrap_or(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)));
-
-        let port = memobot_env_vars::var_parsed("MEMOBOT_API_PORT")
-            .change_context(ApiConfigLoadError)?
-            .unwrap_or(6500);
-
-        Ok(Self { address, port })
-    }
-}
-
-impl ApiConfig {
-    #[must_use]
-    pub fn address(&self) -> IpAddr {
-        self.address
-    }
-
-    #[must_use]
-    pub fn port(&self) -> u16 {
-        self.port
+    // An empty token would let anyone in with an empty bearer token
+    fn admin_token_from_env(
+        key: &'static str,
+    ) -> Result<Option<String>, memobot_env_vars::ReadVarError> {
+        memobot_env_vars::var(key).map(|v| v.filter(|v| !v.is_empty()))
     }
 }
@@ -0,0 +1,81 @@
+use derive_more::Display;
+use error_stack::{Report, Result, ResultExt};
+use memobot_env_vars::FromEnv;
+use twilight_cache_inmemory::ResourceType;
+
+use crate::Suggestion;
+
+#[derive(Debug, Clone, PartialEq, Eq, FromEnv)]
+#[env(error = CacheConfigLoadError)]
+pub struct CacheConfig {
+    /// Maximum number of messages to cache per channel, only
+    /// applies if messages are cached.
+    #[env(key = "MEMOBOT_CACHE_MESSAGE_SIZE", default = 100, copy)]
+    message_cache_size: usize,
+    /// Resources to cache, members and presences also need the
+    /// `GUILD_MEMBERS` and `GUILD_PRESENCES` intents to be enabled.
+    #[env(
+        key = "MEMOBOT_CACHE_RESOURCES",
+        default = Self::default_resource_types(),
+        with = Self::resource_types_from_env,
+        copy
+    )]
+    resource_types: ResourceType,
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "Could not load cache configuration")]
+pub struct CacheConfigLoadError;
+impl error_stack::Context for CacheConfigLoadError {}
+
+impl CacheConfig {
+    #[must_use]
+    fn default_resource_types() -> ResourceType {
+        ResourceType::CHANNEL
+            | ResourceType::GUILD
+            | ResourceType::ROLE
+            | ResourceType::USER_CURRENT
+    }
+
+    fn resource_types_from_env(
+        key: &'static str,
+    ) -> Result<Option<ResourceType>, memobot_env_vars::ReadVarError> {
+        let resources = memobot_env_vars::list_parsed(key, Self::parse_resource_type)?;
+        if resources.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(resources.into_iter().collect()))
+        }
+    }
+
+    fn parse_resource_type(
+        value: &str,
+    ) -> std::result::Result<ResourceType, Report<CacheConfigLoadError>> {
+        let resource_type = match value.to_lowercase().as_str() {
+            "channel" | "channels" => ResourceType::CHANNEL,
+            "emoji" | "emojis" => ResourceType::EMOJI,
+            "guild" | "guilds" => ResourceType::GUILD,
+            "integration" | "integrations" => ResourceType::INTEGRATION,
+            "member" | "members" => ResourceType::MEMBER,
+            "message" | "messages" => ResourceType::MESSAGE,
+            "presence" | "presences" => ResourceType::PRESENCE,
+            "reaction" | "reactions" => ResourceType::REACTION,
+            "role" | "roles" => ResourceType::ROLE,
+            "stage_instance" | "stage_instances" => ResourceType::STAGE_INSTANCE,
+            "sticker" | "stickers" => ResourceType::STICKER,
+            "user" | "users" => ResourceType::USER,
+            "user_current" => ResourceType::USER_CURRENT,
+            "voice_state" | "voice_states" => ResourceType::VOICE_STATE,
+            "all" => ResourceType::all(),
+            "none" => ResourceType::empty(),
+            _ => {
+                return Err(Report::new(CacheConfigLoadError))
+                    .attach(Suggestion::new(
+                        "available resources are: channel, emoji, guild, integration, member, message, presence, reaction, role, stage_instance, sticker, user, user_current, voice_state, all and none",
+                    ))
+                    .attach_printable_lazy(|| format!("unknown cache resource {value:?}"))
+            }
+        };
+        Ok(resource_type)
+    }
+}
@@ -0,0 +1,25 @@
+use derive_more::Display;
+use memobot_env_vars::FromEnv;
+
+#[derive(Debug, Clone, PartialEq, Eq, FromEnv)]
+#[env(error = DatabaseConfigLoadError)]
+pub struct DatabaseConfig {
+    #[env(key = "MEMOBOT_DATABASE_MAX_CONNECTIONS", default = 5, copy)]
+    max_connections: u32,
+    /// SQLite connection URL, the database file will be
+    /// created if it does not exist yet.
+    #[env(key = "MEMOBOT_DATABASE_URL", default = Self::default_url())]
+    url: String,
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "Could not load database configuration")]
+pub struct DatabaseConfigLoadError;
+impl error_stack::Context for DatabaseConfigLoadError {}
+
+impl DatabaseConfig {
+    #[must_use]
+    fn default_url() -> String {
+        "sqlite://memobot.db".into()
+    }
+}
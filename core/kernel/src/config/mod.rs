@@ -1,21 +1,66 @@
 mod api;
+mod cache;
+mod database;
 mod sentry;
 
 pub use api::ApiConfig;
+pub use cache::CacheConfig;
+pub use database::DatabaseConfig;
 pub use sentry::SentryConfig;
 
 use derive_more::Display;
-use error_stack::{Result, ResultExt};
-use twilight_model::id::{marker::ApplicationMarker, Id};
+use error_stack::Result;
+use memobot_env_vars::FromEnv;
+use std::time::Duration;
+use twilight_model::gateway::Intents;
+use twilight_model::id::marker::{ApplicationMarker, GuildMarker};
+use twilight_model::id::Id;
 
 use crate::{Environment, Sensitive};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, FromEnv)]
+#[env(error = BaseConfigLoadError)]
 pub struct Config {
+    #[env(nested)]
     api: ApiConfig,
+    #[env(key = "MEMOBOT_APPLICATION_ID", copy)]
     application_id: Option<Id<ApplicationMarker>>,
+    #[env(nested)]
+    cache: CacheConfig,
+    #[env(nested)]
+    database: DatabaseConfig,
+    /// Guild where commands are registered to instead of globally
+    /// if the bot is not running in production.
+    #[env(key = "MEMOBOT_DEV_GUILD_ID", copy)]
+    dev_guild_id: Option<Id<GuildMarker>>,
+    #[env(key = "MEMOBOT_ENV", default = Environment::from_build(), copy)]
     environment: Environment,
+    /// Gateway intents set with `MEMOBOT_INTENTS`, they are derived
+    /// from what the bot needs if it is not set.
+    #[env(key = "MEMOBOT_INTENTS", with = Self::intents_from_env, copy)]
+    intents: Option<Intents>,
+    /// Discord bot token, `TOKEN` and `MEMOBOT_TOKEN` are checked
+    /// after `DISCORD_TOKEN` in that order.
+    #[env(
+        key = "DISCORD_TOKEN",
+        alias = "TOKEN",
+        alias = "MEMOBOT_TOKEN",
+        required,
+        sensitive
+    )]
     token: Sensitive<String>,
+    /// How long to wait for services and background tasks to stop
+    /// on shutdown before the process is forced to exit. It defaults
+    /// to less than the 10 seconds Docker waits before killing the
+    /// container so the bot still gets to exit on its own.
+    #[env(
+        key = "MEMOBOT_SHUTDOWN_TIMEOUT",
+        default = Duration::from_secs(8),
+        with = Self::shutdown_timeout_from_env,
+        copy
+    )]
+    shutdown_timeout: Duration,
+    #[env(key = "MEMOBOT_WORKERS", default = Self::default_workers(), copy)]
     workers: usize,
 }
 
@@ -25,61 +70,57 @@ pub struct BaseConfigLoadError;
 impl error_stack::Context for BaseConfigLoadError {}
 
 impl Config {
-    pub fn from_env() -> Result<Self, BaseConfigLoadError> {
-        let api = ApiConfig::from_env().change_context(BaseConfigLoadError)?;
-        let application_id = memobot_env_vars::var_parsed("MEMOBOT_APPLICATION_ID")
-            .change_context(BaseConfigLoadError)?;
-
-        let environment = Environment::from_env().change_context(BaseConfigLoadError)?;
-        let token = Self::token_from_env().change_context(BaseConfigLoadError)?;
-
-        let workers = memobot_env_vars::var_parsed("MEMOBOT_WORKERS")
-            .change_context(BaseConfigLoadError)?
-            .unwrap_or_else(Self::default_workers);
-
-        Ok(Self {
-            api,
-            application_id,
-            environment,
-            token: Sensitive::new(token),
-            workers,
-        })
-    }
-}
-
-impl Config {
+    /// Takes the fields from `new` that can be changed while the
+    /// bot is running and keeps the rest from the current
+    /// configuration.
+    ///
+    /// Returns the merged configuration and the fields that have
+    /// changed but only take effect after a restart.
     #[must_use]
-    pub fn api(&self) -> &ApiConfig {
-        &self.api
+    pub(crate) fn reload(&self, new: Config) -> (Config, Vec<&'static str>) {
+        let mut requires_restart = Vec::new();
+        let mut check = |name: &'static str, changed: bool| {
+            if changed {
+                requires_restart.push(name);
+            }
+        };
+
+        check("api", self.api != new.api);
+        check("application_id", self.application_id != new.application_id);
+        check("cache", self.cache != new.cache);
+        check("database", self.database != new.database);
+        // Commands are only synchronized when the bot starts
+        check("dev_guild_id", self.dev_guild_id != new.dev_guild_id);
+        check("environment", self.environment != new.environment);
+        check("intents", self.intents != new.intents);
+        check("token", self.token != new.token);
+        check("workers", self.workers != new.workers);
+
+        let config = Config {
+            shutdown_timeout: new.shutdown_timeout,
+            ..self.clone()
+        };
+
+        (config, requires_restart)
     }
 
-    #[must_use]
-    pub fn application_id(&self) -> Option<Id<ApplicationMarker>> {
-        self.application_id
+    fn intents_from_env(
+        key: &'static str,
+    ) -> Result<Option<Intents>, memobot_env_vars::ReadVarError> {
+        let intents = memobot_env_vars::list_parsed(key, crate::intents::parse)?;
+        if intents.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(intents.into_iter().collect()))
+        }
     }
 
-    #[must_use]
-    pub fn environment(&self) -> Environment {
-        self.environment
-    }
-
-    #[must_use]
-    pub fn token(&self) -> &str {
-        self.token.as_str()
-    }
-
-    #[must_use]
-    pub fn workers(&self) -> usize {
-        self.workers
-    }
-}
-
-impl Config {
-    fn token_from_env() -> Result<String, memobot_env_vars::ReadVarError> {
-        memobot_env_vars::var("DISCORD_TOKEN")
-            .transpose()
-            .or_else(|| memobot_env_vars::var("TOKEN").transpose())
-            .unwrap_or_else(|| memobot_env_vars::required_var("MEMOBOT_TOKEN"))
+    /// Reads the timeout in seconds.
+    fn shutdown_timeout_from_env(
+        key: &'static str,
+    ) -> Result<Option<Duration>, memobot_env_vars::ReadVarError> {
+        let secs = memobot_env_vars::var_parsed::<u64, _>(key)?;
+        Ok(secs.map(Duration::from_secs))
     }
 
     #[must_use]
@@ -0,0 +1,52 @@
+use std::sync::Arc;
+use twilight_model::gateway::ShardId;
+
+use crate::command::CommandRegistry;
+use crate::extension::ExtensionRegistry;
+use crate::Kernel;
+
+/// State given to command and gateway event handlers of a shard.
+#[derive(Debug, Clone)]
+pub struct Context {
+    commands: Arc<CommandRegistry>,
+    extensions: Arc<ExtensionRegistry>,
+    kernel: Kernel,
+    shard_id: ShardId,
+}
+
+impl Context {
+    #[must_use]
+    pub fn new(
+        kernel: &Kernel,
+        commands: Arc<CommandRegistry>,
+        extensions: Arc<ExtensionRegistry>,
+        shard_id: ShardId,
+    ) -> Self {
+        Self {
+            commands,
+            extensions,
+            kernel: kernel.clone(),
+            shard_id,
+        }
+    }
+
+    #[must_use]
+    pub fn commands(&self) -> &CommandRegistry {
+        &self.commands
+    }
+
+    #[must_use]
+    pub fn extensions(&self) -> &ExtensionRegistry {
+        &self.extensions
+    }
+
+    #[must_use]
+    pub fn kernel(&self) -> &Kernel {
+        &self.kernel
+    }
+
+    #[must_use]
+    pub fn shard_id(&self) -> ShardId {
+        self.shard_id
+    }
+}
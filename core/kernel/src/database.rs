@@ -0,0 +1,111 @@
+use error_stack::{Result, ResultExt};
+use sqlx::migrate::Migrator;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use sqlx::SqlitePool;
+use std::str::FromStr;
+
+use crate::config::DatabaseConfig;
+use crate::KernelInitError;
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+#[tracing::instrument(skip_all)]
+pub(crate) async fn connect(config: &DatabaseConfig) -> Result<SqlitePool, KernelInitError> {
+    let options = SqliteConnectOptions::from_str(config.url())
+        .change_context(KernelInitError)
+        .attach_printable("invalid database URL")?
+        .create_if_missing(true);
+
+    let pool = SqlitePoolOptions::new()
+        .max_connections(config.max_connections())
+        .connect_with(options)
+        .await
+        .change_context(KernelInitError)
+        .attach_printable("could not connect to the database")?;
+
+    tracing::debug!("Running database migrations");
+    MIGRATOR
+        .run(&pool)
+        .await
+        .change_context(KernelInitError)
+        .attach_printable("failed to run database migrations")?;
+
+    Ok(pool)
+}
+
+pub(crate) async fn get_state(pool: &SqlitePool, key: &str) -> sqlx::Result<Option<String>> {
+    sqlx::query_scalar("SELECT value FROM kernel_state WHERE key = ?")
+        .bind(key)
+        .fetch_optional(pool)
+        .await
+}
+
+pub(crate) async fn set_state(pool: &SqlitePool, key: &str, value: &str) -> sqlx::Result<()> {
+    sqlx::query(
+        "INSERT INTO kernel_state (key, value) VALUES (?, ?)
+        ON CONFLICT (key) DO UPDATE SET value = excluded.value, updated_at = CURRENT_TIMESTAMP",
+    )
+    .bind(key)
+    .bind(value)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub(crate) async fn remove_state(pool: &SqlitePool, key: &str) -> sqlx::Result<()> {
+    sqlx::query("DELETE FROM kernel_state WHERE key = ?")
+        .bind(key)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claims::{assert_none, assert_ok, assert_some_eq};
+
+    /// In-memory databases are per connection, so the pool
+    /// only keeps one to see the migrated tables.
+    async fn pool() -> SqlitePool {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+
+        assert_ok!(MIGRATOR.run(&pool).await);
+        pool
+    }
+
+    #[tokio::test]
+    async fn migrations_create_tables() {
+        let pool = pool().await;
+
+        let tables: Vec<String> = sqlx::query_scalar(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE '\\_%' ESCAPE '\\' ORDER BY name",
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+
+        assert_eq!(tables, ["gateway_sessions", "kernel_state"]);
+    }
+
+    #[tokio::test]
+    async fn state_round_trip() {
+        let pool = pool().await;
+        assert_none!(assert_ok!(get_state(&pool, "key").await));
+
+        assert_ok!(set_state(&pool, "key", "first").await);
+        assert_some_eq!(assert_ok!(get_state(&pool, "key").await), "first");
+
+        assert_ok!(set_state(&pool, "key", "second").await);
+        assert_some_eq!(assert_ok!(get_state(&pool, "key").await), "second");
+
+        assert_ok!(remove_state(&pool, "key").await);
+        assert_none!(assert_ok!(get_state(&pool, "key").await));
+    }
+}
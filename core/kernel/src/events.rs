@@ -0,0 +1,170 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use tokio::sync::mpsc::{self, error::TrySendError};
+use twilight_model::gateway::event::{Event, EventType};
+use twilight_model::gateway::ShardId;
+
+/// How many events can be queued for a subscriber before
+/// new events are dropped for that subscriber.
+const SUBSCRIBER_CAPACITY: usize = 256;
+
+/// Gateway event received from a shard.
+#[derive(Debug, Clone)]
+pub struct GatewayEvent {
+    pub shard_id: ShardId,
+    pub event: Arc<Event>,
+}
+
+struct Subscriber {
+    name: &'static str,
+    kinds: HashSet<EventType>,
+    sender: mpsc::Sender<GatewayEvent>,
+}
+
+/// Publish/subscribe bus for gateway events received by all shards.
+///
+/// Every subscriber has its own bounded queue, so a subscriber
+/// that cannot keep up only misses its own events instead of
+/// stalling the shard that published them.
+#[derive(Default)]
+pub struct EventBus {
+    subscribers: Mutex<Vec<Subscriber>>,
+}
+
+impl EventBus {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subscribes to gateway events with the given kinds.
+    ///
+    /// The subscription is removed once the returned
+    /// [`EventSubscription`] is dropped.
+    #[must_use]
+    pub fn subscribe(&self, name: &'static str, kinds: &[EventType]) -> EventSubscription {
+        let (sender, receiver) = mpsc::channel(SUBSCRIBER_CAPACITY);
+        let subscriber = Subscriber {
+            name,
+            kinds: kinds.iter().copied().collect(),
+            sender,
+        };
+
+        self.subscribers
+            .lock()
+            .expect("event bus lock is poisoned")
+            .push(subscriber);
+
+        EventSubscription { receiver }
+    }
+
+    /// Sends the event to every subscriber that is interested
+    /// in it without waiting for them.
+    pub fn publish(&self, shard_id: ShardId, event: &Event) {
+        let kind = event.kind();
+        let mut shared = None;
+
+        let mut subscribers = self.subscribers.lock().expect("event bus lock is poisoned");
+        subscribers.retain(|subscriber| {
+            if !subscriber.kinds.contains(&kind) {
+                return !subscriber.sender.is_closed();
+            }
+
+            let event = shared
+                .get_or_insert_with(|| Arc::new(event.clone()))
+                .clone();
+            match subscriber.sender.try_send(GatewayEvent { shard_id, event }) {
+                Ok(..) => true,
+                Err(TrySendError::Full(..)) => {
+                    tracing::warn!(
+                        subscriber = %subscriber.name,
+                        event.kind = ?kind,
+                        "Subscriber is falling behind, dropping event"
+                    );
+                    true
+                }
+                Err(TrySendError::Closed(..)) => {
+                    tracing::debug!(subscriber = %subscriber.name, "Removing closed subscriber");
+                    false
+                }
+            }
+        });
+    }
+
+    /// Every subscriber's name with an event kind it subscribed to.
+    #[must_use]
+    pub fn subscriptions(&self) -> Vec<(&'static str, EventType)> {
+        self.subscribers
+            .lock()
+            .expect("event bus lock is poisoned")
+            .iter()
+            .flat_map(|v| v.kinds.iter().map(|kind| (v.name, *kind)))
+            .collect()
+    }
+
+    #[must_use]
+    pub fn subscribers(&self) -> usize {
+        self.subscribers
+            .lock()
+            .expect("event bus lock is poisoned")
+            .len()
+    }
+}
+
+impl std::fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventBus")
+            .field("subscribers", &self.subscribers())
+            .finish()
+    }
+}
+
+/// Receiving end of an [`EventBus`] subscription.
+#[derive(Debug)]
+pub struct EventSubscription {
+    receiver: mpsc::Receiver<GatewayEvent>,
+}
+
+impl EventSubscription {
+    /// Waits for the next event. Returns `None` if the
+    /// event bus has been dropped.
+    pub async fn recv(&mut self) -> Option<GatewayEvent> {
+        self.receiver.recv().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHARD_ID: ShardId = ShardId::ONE;
+
+    #[test]
+    fn full_subscriber_drops_events() {
+        let events = EventBus::new();
+        let mut subscription = events.subscribe("test", &[EventType::GatewayHeartbeatAck]);
+
+        for _ in 0..SUBSCRIBER_CAPACITY + 1 {
+            events.publish(SHARD_ID, &Event::GatewayHeartbeatAck);
+        }
+        assert_eq!(events.subscribers(), 1);
+
+        let mut received = 0;
+        while subscription.receiver.try_recv().is_ok() {
+            received += 1;
+        }
+        assert_eq!(received, SUBSCRIBER_CAPACITY);
+    }
+
+    #[test]
+    fn dropped_subscriber_is_removed() {
+        let events = EventBus::new();
+        let subscription = events.subscribe("test", &[EventType::GatewayHeartbeatAck]);
+        let _other = events.subscribe("other", &[EventType::Resumed]);
+        drop(subscription);
+
+        events.publish(SHARD_ID, &Event::GatewayHeartbeatAck);
+        assert_eq!(events.subscribers(), 1);
+        assert_eq!(events.subscriptions(), [("other", EventType::Resumed)]);
+    }
+}
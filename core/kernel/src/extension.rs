@@ -0,0 +1,222 @@
+use actix_web::web::ServiceConfig;
+use async_trait::async_trait;
+use derive_more::Display;
+use error_stack::{Result, ResultExt};
+use std::sync::Arc;
+use twilight_model::gateway::event::Event;
+use twilight_model::gateway::Intents;
+
+use crate::command::CommandRegistry;
+use crate::{Context, Kernel};
+
+/// A self-contained feature of the bot that lives on its own crate.
+///
+/// Every part of the extension is optional except for its name
+/// and how it is loaded. To enable an extension, load it into the
+/// [`ExtensionRegistry`] before the bot starts.
+#[async_trait]
+pub trait Extension: std::fmt::Debug + Send + Sync + 'static {
+    /// Name of the extension, its API routes are
+    /// mounted under `/{name}`.
+    fn name(&self) -> &'static str;
+
+    /// Same as [`Extension::name`], for when the extension has
+    /// not been loaded yet.
+    fn static_name() -> &'static str
+    where
+        Self: Sized;
+
+    /// Loads the extension's configuration and creates the extension.
+    ///
+    /// Returns `Ok(None)` if the extension is not configured.
+    fn load(kernel: &Kernel) -> Result<Option<Self>, ExtensionLoadError>
+    where
+        Self: Sized;
+
+    /// Gateway intents the extension needs to receive its events.
+    fn intents(&self) -> Intents {
+        Intents::empty()
+    }
+
+    /// Adds the extension's routes to the API server.
+    fn configure_api(&self, _cfg: &mut ServiceConfig) {}
+
+    /// Registers the extension's slash commands.
+    fn register_commands(&self, _registry: &mut CommandRegistry) {}
+
+    /// Called for every gateway event received by any shard.
+    async fn on_event(&self, _ctx: &Context, _event: &Event) {}
+
+    /// Spawns the extension's background tasks with [`Kernel::spawn`].
+    /// Tasks can subscribe to [`Kernel::events`] to react to gateway events,
+    /// and [`Kernel::on_shutdown`] to run work while the bot shuts down.
+    ///
+    /// It is called once before the bot starts.
+    fn start(&self, _kernel: &Kernel) {}
+
+    /// Reads and validates the extension's configuration while the
+    /// bot is running without applying it yet.
+    ///
+    /// The returned [`PendingReload`] is only applied once the
+    /// configuration of the kernel and every extension is valid.
+    fn reload(&self, _kernel: &Kernel) -> Result<PendingReload, ExtensionLoadError> {
+        Ok(PendingReload::default())
+    }
+}
+
+/// Validated configuration of an extension that is waiting to be
+/// applied, returned by [`Extension::reload`].
+#[derive(Default)]
+pub struct PendingReload {
+    apply: Option<Box<dyn FnOnce() + Send>>,
+    requires_restart: Vec<&'static str>,
+}
+
+impl PendingReload {
+    /// Runs `apply` once every configuration has been validated.
+    #[must_use]
+    pub fn new(apply: impl FnOnce() + Send + 'static) -> Self {
+        Self {
+            apply: Some(Box::new(apply)),
+            requires_restart: Vec::new(),
+        }
+    }
+
+    /// Keys of the extension's table that have changed but
+    /// require a restart to take effect.
+    #[must_use]
+    pub fn requires_restart(mut self, keys: impl IntoIterator<Item = &'static str>) -> Self {
+        self.requires_restart.extend(keys);
+        self
+    }
+
+    /// Applies the configuration and returns the keys that
+    /// require a restart.
+    pub fn apply(self) -> Vec<&'static str> {
+        if let Some(apply) = self.apply {
+            apply();
+        }
+        self.requires_restart
+    }
+}
+
+impl std::fmt::Debug for PendingReload {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PendingReload")
+            .field("requires_restart", &self.requires_restart)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Validated configurations of every extension, returned
+/// by [`ExtensionRegistry::reload`].
+#[derive(Debug, Default)]
+pub struct PendingReloads(Vec<(&'static str, PendingReload)>);
+
+impl PendingReloads {
+    /// Applies every configuration and returns the keys that
+    /// require a restart, prefixed with the extension's name.
+    pub fn apply(self) -> Vec<String> {
+        let mut requires_restart = Vec::new();
+        for (name, reload) in self.0 {
+            let keys = reload.apply();
+            requires_restart.extend(keys.into_iter().map(|key| format!("{name}.{key}")));
+        }
+        requires_restart
+    }
+}
+
+#[derive(Debug, Display)]
+#[display(fmt = "Could not load extension")]
+pub struct ExtensionLoadError;
+impl error_stack::Context for ExtensionLoadError {}
+
+/// List of all loaded extensions.
+#[derive(Debug, Default)]
+pub struct ExtensionRegistry {
+    extensions: Vec<Arc<dyn Extension>>,
+}
+
+impl ExtensionRegistry {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads an extension and adds it to the registry if
+    /// it is configured.
+    pub fn load<E: Extension>(&mut self, kernel: &Kernel) -> Result<(), ExtensionLoadError> {
+        let extension = E::load(kernel)
+            .attach_printable_lazy(|| format!("failed to load {} extension", E::static_name()))?;
+
+        let Some(extension) = extension else {
+            tracing::info!("{} extension is disabled", E::static_name());
+            return Ok(());
+        };
+
+        tracing::info!("Loaded {} extension", extension.name());
+        self.extensions.push(Arc::new(extension));
+        Ok(())
+    }
+}
+
+impl ExtensionRegistry {
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<dyn Extension>> {
+        self.extensions.iter()
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.extensions.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.extensions.is_empty()
+    }
+}
+
+impl ExtensionRegistry {
+    /// Mounts every extension's routes under `/{name}`.
+    pub fn configure_api(&self, cfg: &mut ServiceConfig) {
+        for extension in self.iter() {
+            let scope = actix_web::web::scope(&format!("/{}", extension.name()))
+                .configure(|cfg| extension.configure_api(cfg));
+
+            cfg.service(scope);
+        }
+    }
+
+    pub fn register_commands(&self, registry: &mut CommandRegistry) {
+        for extension in self.iter() {
+            extension.register_commands(registry);
+        }
+    }
+
+    pub async fn on_event(&self, ctx: &Context, event: &Event) {
+        for extension in self.iter() {
+            extension.on_event(ctx, event).await;
+        }
+    }
+
+    pub fn start(&self, kernel: &Kernel) {
+        for extension in self.iter() {
+            extension.start(kernel);
+        }
+    }
+
+    /// Reads and validates every extension's configuration, nothing
+    /// is applied until [`PendingReloads::apply`] is called.
+    pub fn reload(&self, kernel: &Kernel) -> Result<PendingReloads, ExtensionLoadError> {
+        let mut pending = PendingReloads::default();
+        for extension in self.iter() {
+            let name = extension.name();
+            let reload = extension
+                .reload(kernel)
+                .attach_printable_lazy(|| format!("failed to reload {name} extension"))?;
+
+            pending.0.push((name, reload));
+        }
+        Ok(pending)
+    }
+}
@@ -0,0 +1,368 @@
+use derive_more::Display;
+use error_stack::{Report, Result};
+use twilight_cache_inmemory::ResourceType;
+use twilight_model::gateway::event::EventType;
+use twilight_model::gateway::Intents;
+
+use crate::events::EventBus;
+use crate::extension::ExtensionRegistry;
+use crate::{Config, Suggestion};
+
+/// Intents that memobot itself needs regardless of which
+/// extensions are loaded.
+pub const CORE_INTENTS: Intents = Intents::GUILDS.union(Intents::GUILD_INTEGRATIONS);
+
+/// Intents that must be enabled in the Discord Developer Portal
+/// before the bot can use them.
+pub const PRIVILEGED_INTENTS: Intents = Intents::GUILD_MEMBERS
+    .union(Intents::GUILD_PRESENCES)
+    .union(Intents::MESSAGE_CONTENT);
+
+const NAMES: &[(&str, Intents)] = &[
+    ("GUILDS", Intents::GUILDS),
+    ("GUILD_MEMBERS", Intents::GUILD_MEMBERS),
+    ("GUILD_MODERATION", Intents::GUILD_MODERATION),
+    (
+        "GUILD_EMOJIS_AND_STICKERS",
+        Intents::GUILD_EMOJIS_AND_STICKERS,
+    ),
+    ("GUILD_INTEGRATIONS", Intents::GUILD_INTEGRATIONS),
+    ("GUILD_WEBHOOKS", Intents::GUILD_WEBHOOKS),
+    ("GUILD_INVITES", Intents::GUILD_INVITES),
+    ("GUILD_VOICE_STATES", Intents::GUILD_VOICE_STATES),
+    ("GUILD_PRESENCES", Intents::GUILD_PRESENCES),
+    ("GUILD_MESSAGES", Intents::GUILD_MESSAGES),
+    ("GUILD_MESSAGE_REACTIONS", Intents::GUILD_MESSAGE_REACTIONS),
+    ("GUILD_MESSAGE_TYPING", Intents::GUILD_MESSAGE_TYPING),
+    ("DIRECT_MESSAGES", Intents::DIRECT_MESSAGES),
+    (
+        "DIRECT_MESSAGE_REACTIONS",
+        Intents::DIRECT_MESSAGE_REACTIONS,
+    ),
+    ("DIRECT_MESSAGE_TYPING", Intents::DIRECT_MESSAGE_TYPING),
+    ("MESSAGE_CONTENT", Intents::MESSAGE_CONTENT),
+    ("GUILD_SCHEDULED_EVENTS", Intents::GUILD_SCHEDULED_EVENTS),
+    (
+        "AUTO_MODERATION_CONFIGURATION",
+        Intents::AUTO_MODERATION_CONFIGURATION,
+    ),
+    (
+        "AUTO_MODERATION_EXECUTION",
+        Intents::AUTO_MODERATION_EXECUTION,
+    ),
+];
+
+/// Cached resources that are only filled in from events that
+/// are not delivered without the intent.
+const CACHE_INTENTS: &[(&str, ResourceType, Intents)] = &[
+    ("member", ResourceType::MEMBER, Intents::GUILD_MEMBERS),
+    ("presence", ResourceType::PRESENCE, Intents::GUILD_PRESENCES),
+];
+
+#[derive(Debug, Display)]
+#[display(fmt = "Could not parse gateway intent")]
+pub struct IntentParseError;
+impl error_stack::Context for IntentParseError {}
+
+#[derive(Debug, Display)]
+#[display(fmt = "Configured gateway intents do not cover every event handler")]
+pub struct IntentsError;
+impl error_stack::Context for IntentsError {}
+
+/// Parses an intent by its name in Discord's documentation,
+/// for example `GUILD_MEMBERS`.
+pub fn parse(value: &str) -> std::result::Result<Intents, Report<IntentParseError>> {
+    let value = value.to_uppercase();
+    NAMES
+        .iter()
+        .find(|(name, _)| *name == value)
+        .map(|(_, intent)| *intent)
+        .ok_or_else(|| {
+            Report::new(IntentParseError)
+                .attach(Suggestion::new(
+                    "use intent names from Discord's documentation, such as GUILDS or GUILD_MEMBERS",
+                ))
+                .attach_printable(format!("unknown intent {value:?}"))
+        })
+}
+
+/// Lists the intents by name, privileged intents are marked.
+#[must_use]
+pub fn describe(intents: Intents) -> String {
+    let names = NAMES
+        .iter()
+        .filter(|(_, intent)| intents.contains(*intent))
+        .map(|(name, intent)| {
+            if PRIVILEGED_INTENTS.contains(*intent) {
+                format!("{name} (privileged)")
+            } else {
+                (*name).to_string()
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if names.is_empty() {
+        "none".into()
+    } else {
+        names.join(", ")
+    }
+}
+
+/// Intents where at least one of them has to be enabled to receive
+/// events of this kind. Returns `None` if the event is always sent.
+#[must_use]
+pub fn required_for(kind: EventType) -> Option<Intents> {
+    use EventType::*;
+
+    let intents = match kind {
+        GuildCreate | GuildUpdate | GuildDelete | RoleCreate | RoleUpdate | RoleDelete
+        | ChannelCreate | ChannelUpdate | ChannelDelete | ThreadCreate | ThreadUpdate
+        | ThreadDelete | ThreadListSync | ThreadMemberUpdate | StageInstanceCreate
+        | StageInstanceUpdate | StageInstanceDelete => Intents::GUILDS,
+        ChannelPinsUpdate => Intents::GUILDS | Intents::DIRECT_MESSAGES,
+        MemberAdd | MemberUpdate | MemberRemove | ThreadMembersUpdate => Intents::GUILD_MEMBERS,
+        BanAdd | BanRemove | GuildAuditLogEntryCreate => Intents::GUILD_MODERATION,
+        GuildEmojisUpdate | GuildStickersUpdate => Intents::GUILD_EMOJIS_AND_STICKERS,
+        GuildIntegrationsUpdate | IntegrationCreate | IntegrationUpdate | IntegrationDelete => {
+            Intents::GUILD_INTEGRATIONS
+        }
+        WebhooksUpdate => Intents::GUILD_WEBHOOKS,
+        InviteCreate | InviteDelete => Intents::GUILD_INVITES,
+        VoiceStateUpdate => Intents::GUILD_VOICE_STATES,
+        PresenceUpdate => Intents::GUILD_PRESENCES,
+        MessageCreate | MessageUpdate | MessageDelete => {
+            Intents::GUILD_MESSAGES | Intents::DIRECT_MESSAGES
+        }
+        MessageDeleteBulk => Intents::GUILD_MESSAGES,
+        ReactionAdd | ReactionRemove | ReactionRemoveAll | ReactionRemoveEmoji => {
+            Intents::GUILD_MESSAGE_REACTIONS | Intents::DIRECT_MESSAGE_REACTIONS
+        }
+        TypingStart => Intents::GUILD_MESSAGE_TYPING | Intents::DIRECT_MESSAGE_TYPING,
+        GuildScheduledEventCreate
+        | GuildScheduledEventUpdate
+        | GuildScheduledEventDelete
+        | GuildScheduledEventUserAdd
+        | GuildScheduledEventUserRemove => Intents::GUILD_SCHEDULED_EVENTS,
+        AutoModerationRuleCreate | AutoModerationRuleUpdate | AutoModerationRuleDelete => {
+            Intents::AUTO_MODERATION_CONFIGURATION
+        }
+        AutoModerationActionExecution => Intents::AUTO_MODERATION_EXECUTION,
+        _ => return None,
+    };
+
+    Some(intents)
+}
+
+/// Figures out which intents the shards should connect with.
+///
+/// If `MEMOBOT_INTENTS` is set, it is used as is. Otherwise, it
+/// is derived from the intents that memobot and the loaded
+/// extensions need. Either way, memobot itself, every extension and
+/// event bus subscriber must be able to receive what it asked for,
+/// and so must the cache for the resources it is configured with.
+pub fn resolve(
+    config: &Config,
+    extensions: &ExtensionRegistry,
+    events: &EventBus,
+) -> Result<Intents, IntentsError> {
+    let extensions = extensions
+        .iter()
+        .map(|v| (v.name(), v.intents()))
+        .collect::<Vec<_>>();
+
+    resolve_with(
+        config.intents(),
+        config.cache().resource_types(),
+        &extensions,
+        events,
+    )
+}
+
+/// Same as [`resolve`] with the intents configured with
+/// `MEMOBOT_INTENTS`, the cached resources and the intents
+/// each extension needs.
+fn resolve_with(
+    configured: Option<Intents>,
+    cached: ResourceType,
+    extensions: &[(&'static str, Intents)],
+    events: &EventBus,
+) -> Result<Intents, IntentsError> {
+    let declared = extensions
+        .iter()
+        .fold(CORE_INTENTS, |intents, (_, needed)| intents | *needed);
+
+    let intents = configured.unwrap_or(declared);
+    let mut problems = Vec::new();
+
+    let missing = CORE_INTENTS - intents;
+    if !missing.is_empty() {
+        problems.push(format!(
+            "memobot needs intents that are not enabled: {}",
+            describe(missing)
+        ));
+    }
+
+    for (name, needed) in extensions {
+        let missing = *needed - intents;
+        if !missing.is_empty() {
+            problems.push(format!(
+                "extension {name:?} needs intents that are not enabled: {}",
+                describe(missing)
+            ));
+        }
+    }
+
+    for (name, resource, required) in CACHE_INTENTS {
+        if cached.contains(*resource) && !intents.contains(*required) {
+            problems.push(format!(
+                "{name} resources are cached but never received without: {}",
+                describe(*required)
+            ));
+        }
+    }
+
+    for (subscriber, kind) in events.subscriptions() {
+        let Some(required) = required_for(kind) else {
+            continue;
+        };
+
+        if !intents.intersects(required) {
+            problems.push(format!(
+                "{subscriber:?} subscribes to {kind:?} events which are never delivered without one of: {}",
+                describe(required)
+            ));
+        }
+    }
+
+    if problems.is_empty() {
+        return Ok(intents);
+    }
+
+    let mut report = Report::new(IntentsError)
+        .attach(Suggestion::new(
+            "add the missing intents to MEMOBOT_INTENTS or declare them in Extension::intents, privileged intents must be enabled in the Discord Developer Portal too. Resources that cannot be received can be removed from MEMOBOT_CACHE_RESOURCES instead",
+        ))
+        .attach_printable(format!("enabled intents: {}", describe(intents)));
+
+    for problem in problems {
+        report = report.attach_printable(problem);
+    }
+
+    Err(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claims::{assert_err, assert_ok, assert_ok_eq};
+
+    /// Problems attached to the error returned by [`resolve_with`].
+    fn problems(result: Result<Intents, IntentsError>) -> String {
+        format!("{:?}", assert_err!(result))
+    }
+
+    #[test]
+    fn parse_is_case_insensitive() {
+        assert_ok_eq!(parse("GUILD_MEMBERS"), Intents::GUILD_MEMBERS);
+        assert_ok_eq!(parse("message_content"), Intents::MESSAGE_CONTENT);
+        assert!(parse("GUILD_MEMBER").is_err());
+        assert!(parse("").is_err());
+    }
+
+    #[test]
+    fn describe_marks_privileged_intents() {
+        assert_eq!(describe(Intents::empty()), "none");
+        assert_eq!(
+            describe(Intents::GUILDS | Intents::GUILD_MEMBERS),
+            "GUILDS, GUILD_MEMBERS (privileged)"
+        );
+    }
+
+    #[test]
+    fn resolve_derives_intents_from_extensions() {
+        let extensions = [("paradise", Intents::GUILD_MESSAGES)];
+        let intents = assert_ok!(resolve_with(
+            None,
+            ResourceType::empty(),
+            &extensions,
+            &EventBus::new()
+        ));
+        assert_eq!(intents, CORE_INTENTS | Intents::GUILD_MESSAGES);
+    }
+
+    #[test]
+    fn resolve_uses_configured_intents() {
+        let configured = CORE_INTENTS | Intents::GUILD_MESSAGES | Intents::DIRECT_MESSAGES;
+        let extensions = [("paradise", Intents::GUILD_MESSAGES)];
+
+        let intents = assert_ok!(resolve_with(
+            Some(configured),
+            ResourceType::empty(),
+            &extensions,
+            &EventBus::new()
+        ));
+        assert_eq!(intents, configured);
+    }
+
+    #[test]
+    fn resolve_requires_core_intents() {
+        let configured = Intents::GUILD_MESSAGES;
+        let problems = problems(resolve_with(
+            Some(configured),
+            ResourceType::empty(),
+            &[],
+            &EventBus::new(),
+        ));
+        assert!(problems
+            .contains("memobot needs intents that are not enabled: GUILDS, GUILD_INTEGRATIONS"));
+    }
+
+    #[test]
+    fn resolve_requires_extension_intents() {
+        let extensions = [("paradise", Intents::GUILD_MESSAGES)];
+        let problems = problems(resolve_with(
+            Some(CORE_INTENTS),
+            ResourceType::empty(),
+            &extensions,
+            &EventBus::new(),
+        ));
+        assert!(problems.contains("extension \"paradise\" needs intents"));
+    }
+
+    #[test]
+    fn resolve_requires_subscribed_events() {
+        let events = EventBus::new();
+        let _subscription = events.subscribe("test", &[EventType::MessageCreate]);
+
+        let problems = problems(resolve_with(
+            Some(CORE_INTENTS),
+            ResourceType::empty(),
+            &[],
+            &events,
+        ));
+        assert!(problems.contains("\"test\" subscribes to MessageCreate events"));
+
+        let configured = CORE_INTENTS | Intents::DIRECT_MESSAGES;
+        assert_ok!(resolve_with(
+            Some(configured),
+            ResourceType::empty(),
+            &[],
+            &events
+        ));
+    }
+    #[test]
+    fn resolve_requires_cached_resource_intents() {
+        let cached = ResourceType::GUILD | ResourceType::MEMBER;
+        let problems = problems(resolve_with(None, cached, &[], &EventBus::new()));
+        assert!(problems
+            .contains("member resources are cached but never received without: GUILD_MEMBERS"));
+
+        let configured = CORE_INTENTS | Intents::GUILD_MEMBERS;
+        assert_ok!(resolve_with(
+            Some(configured),
+            cached,
+            &[],
+            &EventBus::new()
+        ));
+    }
+}
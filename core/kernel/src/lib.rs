@@ -2,40 +2,71 @@ use cfg_if::cfg_if;
 use derive_more::Display;
 use error_stack::{FutureExt, Report, Result, ResultExt};
 use futures::{Future, TryFutureExt};
+use sqlx::SqlitePool;
 use std::fmt::Display;
 use std::future::IntoFuture;
+use std::panic::Location;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tokio::task::JoinHandle;
 use tokio_util::sync::CancellationToken;
 use tokio_util::task::task_tracker::TaskTrackerWaitFuture;
 use tokio_util::task::TaskTracker;
+use twilight_cache_inmemory::InMemoryCache;
 use twilight_model::gateway::ShardId;
 use twilight_model::id::{marker::ApplicationMarker, Id};
 
+mod context;
+mod database;
 mod sensitive;
 mod suggestion;
+mod tasks;
 
+pub mod auth;
+pub mod command;
 pub mod config;
+pub mod events;
+pub mod extension;
+pub mod intents;
+pub mod metrics;
 
 pub use self::config::Config;
+pub use self::context::Context;
+pub use self::extension::{Extension, ExtensionRegistry};
+pub use self::metrics::Metrics;
 pub use self::sensitive::Sensitive;
+pub use self::shutdown::ShutdownPhase;
 pub use self::suggestion::Suggestion;
+pub use self::tasks::BackgroundTask;
 
 ///////////////////////////////////////////////////////////////////////
 mod shutdown;
 
+const APPLICATION_ID_KEY: &str = "application_id";
+
 #[derive(Clone)]
 pub struct Kernel {
     application_id: Arc<RwLock<Id<ApplicationMarker>>>,
     // Background tasks made from the API to implement things
     // like graceful shutdowns
     background_tasks: TaskTracker,
-    config: Arc<config::Config>,
+    background_task_list: Arc<tasks::TaskList>,
+    cache: Arc<InMemoryCache>,
+    config: Arc<std::sync::RwLock<Arc<config::Config>>>,
+    database: SqlitePool,
+    events: Arc<events::EventBus>,
     http: Arc<twilight_http::Client>,
+    metrics: Metrics,
     shutdown: CancellationToken,
+    shutdown_reason: Arc<std::sync::OnceLock<ShutdownReason>>,
+    shutdown_hooks: Arc<std::sync::Mutex<Vec<(ShutdownPhase, shutdown::ShutdownHook)>>>,
 }
 
+#[derive(Debug, Display)]
+#[display(fmt = "Background task failed")]
+pub struct TaskError;
+impl error_stack::Context for TaskError {}
+
 #[derive(Debug, Display)]
 #[display(fmt = "Could not initialize memobot kernel")]
 pub struct KernelInitError;
@@ -43,37 +74,70 @@ impl error_stack::Context for KernelInitError {}
 
 impl Kernel {
     pub async fn init(config: config::Config) -> Result<Self, KernelInitError> {
+        let metrics = Metrics::new();
         let http = twilight_http::Client::builder()
             .token(config.token().into())
+            .ratelimiter(Some(Box::new(metrics.ratelimiter())))
             .build();
 
-        let application_id = Self::get_application_id(&config, &http).await?;
+        let database = database::connect(config.database()).await?;
+        let application_id = Self::get_application_id(&config, &database, &http).await?;
+        let cache = InMemoryCache::builder()
+            .resource_types(config.cache().resource_types())
+            .message_cache_size(config.cache().message_cache_size())
+            .build();
 
         Ok(Self {
             application_id: Arc::new(RwLock::new(application_id)),
             background_tasks: TaskTracker::new(),
-            config: Arc::new(config),
+            background_task_list: Arc::default(),
+            cache: Arc::new(cache),
+            config: Arc::new(std::sync::RwLock::new(Arc::new(config))),
+            database,
+            events: Arc::new(events::EventBus::new()),
             http: Arc::new(http),
+            metrics,
             shutdown: CancellationToken::new(),
+            shutdown_reason: Arc::default(),
+            shutdown_hooks: Arc::default(),
         })
     }
 
     async fn get_application_id(
         config: &config::Config,
+        database: &SqlitePool,
         http: &twilight_http::Client,
     ) -> Result<Id<ApplicationMarker>, KernelInitError> {
         if let Some(id) = config.application_id() {
             return Ok(id);
         }
 
+        let stored = database::get_state(database, APPLICATION_ID_KEY)
+            .await
+            .change_context(KernelInitError)
+            .attach_printable("failed to read stored application ID")?;
+
+        if let Some(id) = stored.and_then(|v| v.parse().ok()) {
+            tracing::debug!("Using stored application ID from the database");
+            return Ok(id);
+        }
+
         tracing::warn!("MEMOBOT_APPLICATION_ID is missing, getting application ID from Discord");
-        http.current_user_application()
+        let id = http
+            .current_user_application()
             .into_future()
             .change_context(KernelInitError)
             .and_then(|v| v.model().change_context(KernelInitError))
             .map_ok(|v| v.id)
             .await
-            .attach_printable("failed to get application ID of a bot from Discord API")
+            .attach_printable("failed to get application ID of a bot from Discord API")?;
+
+        if let Err(error) = database::set_state(database, APPLICATION_ID_KEY, &id.to_string()).await
+        {
+            tracing::warn!(?error, "Failed to store application ID in the database");
+        }
+
+        Ok(id)
     }
 }
 
@@ -83,9 +147,35 @@ impl Kernel {
         *self.application_id.read().await
     }
 
+    /// In-memory cache of resources received from the gateway.
+    ///
+    /// Which resources are cached is configured with
+    /// `MEMOBOT_CACHE_RESOURCES`.
+    #[must_use]
+    pub fn cache(&self) -> &InMemoryCache {
+        &self.cache
+    }
+
+    /// Snapshot of the current configuration.
+    ///
+    /// The configuration may be replaced with [`Kernel::reload_config`]
+    /// while the bot is running, so avoid holding the snapshot for
+    /// too long.
+    #[must_use]
+    pub fn config(&self) -> Arc<config::Config> {
+        self.config.read().expect("config lock is poisoned").clone()
+    }
+
+    /// Connection pool of the bot's SQLite database.
+    #[must_use]
+    pub fn database(&self) -> &SqlitePool {
+        &self.database
+    }
+
+    /// Gateway events received from all shards.
     #[must_use]
-    pub fn config(&self) -> &config::Config {
-        &self.config
+    pub fn events(&self) -> &events::EventBus {
+        &self.events
     }
 
     #[must_use]
@@ -93,6 +183,12 @@ impl Kernel {
         &self.http
     }
 
+    /// Prometheus metrics exposed on `/metrics`.
+    #[must_use]
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
     #[must_use]
     pub async fn interaction(&self) -> twilight_http::client::InteractionClient<'_> {
         self.http.interaction(*self.application_id.read().await)
@@ -100,6 +196,9 @@ impl Kernel {
 }
 
 impl Kernel {
+    /// Spawns a background task that runs inside the current tracing
+    /// span and a Sentry hub forked from the current one, so events
+    /// and errors of the task keep their context.
     #[track_caller]
     #[inline]
     pub fn spawn<F>(&self, task: F) -> JoinHandle<F::Output>
@@ -107,7 +206,17 @@ impl Kernel {
         F: Future + Send + 'static,
         F::Output: Send + 'static,
     {
-        self.background_tasks.spawn(task)
+        use sentry::SentryFutureExt;
+        use tracing::Instrument;
+
+        let guard = self.background_task_list.track(Location::caller(), false);
+        let hub = Arc::new(sentry::Hub::new_from_top(sentry::Hub::current()));
+        let task = async move {
+            let _guard = guard;
+            task.await
+        };
+        self.background_tasks
+            .spawn(task.instrument(tracing::Span::current()).bind_hub(hub))
     }
 
     #[track_caller]
@@ -118,7 +227,11 @@ impl Kernel {
         F: Send + 'static,
         T: Send + 'static,
     {
-        self.background_tasks.spawn_blocking(task)
+        let guard = self.background_task_list.track(Location::caller(), true);
+        self.background_tasks.spawn_blocking(move || {
+            let _guard = guard;
+            task()
+        })
     }
 
     #[must_use]
@@ -126,36 +239,81 @@ impl Kernel {
         self.background_tasks.len()
     }
 
+    /// Background tasks that are still running, ordered by
+    /// when they were spawned.
+    #[must_use]
+    pub fn background_tasks(&self) -> Vec<BackgroundTask> {
+        self.background_task_list.snapshot()
+    }
+
     pub async fn close_background_tasks_and_wait(&self) -> TaskTrackerWaitFuture<'_> {
         self.background_tasks.close();
         self.background_tasks.wait()
     }
 
-    // #[must_use]
-    // #[inline]
-    // pub async fn spawn_blocking_result<F, T>(&self, task: F) -> Result<T>
-    // where
-    //     F: FnOnce() -> Result<T>,
-    //     F: Send + 'static,
-    //     T: Send + 'static,
-    // {
-    //     use sentry::Hub;
-    //     use std::convert::identity;
-
-    //     let current_span = tracing::Span::current();
-    //     let hub = sentry::Hub::current();
-    //     self.tasks
-    //         .spawn_blocking(move || current_span.in_scope(|| Hub::run(hub, task)))
-    //         .await
-    //         .map_err(anyhow::Error::new)
-    //         .and_then(identity)
-    // }
+    /// Runs blocking work like [`Kernel::spawn_blocking`] inside the
+    /// current tracing span and Sentry hub, and flattens its result
+    /// with the error of the task itself.
+    #[track_caller]
+    pub fn spawn_blocking_result<F, T, C>(
+        &self,
+        task: F,
+    ) -> impl Future<Output = Result<T, TaskError>>
+    where
+        F: FnOnce() -> Result<T, C>,
+        F: Send + 'static,
+        T: Send + 'static,
+        C: error_stack::Context,
+    {
+        let span = tracing::Span::current();
+        let hub = Arc::new(sentry::Hub::new_from_top(sentry::Hub::current()));
+        let handle = self.spawn_blocking(move || span.in_scope(|| sentry::Hub::run(hub, task)));
+
+        async move {
+            handle
+                .await
+                .change_context(TaskError)
+                .attach_printable("blocking task has panicked or was cancelled")?
+                .change_context(TaskError)
+        }
+    }
 }
 
 impl Kernel {
+    /// Applies fields of `new` that are safe to change while the
+    /// bot is running.
+    ///
+    /// Returns the names of the fields that have changed but
+    /// require a restart to take effect.
+    pub fn reload_config(&self, new: config::Config) -> Vec<&'static str> {
+        let mut config = self.config.write().expect("config lock is poisoned");
+        let (reloaded, requires_restart) = config.reload(new);
+        *config = Arc::new(reloaded);
+        requires_restart
+    }
+
+    /// Value stored under `key` in the key-value store that is
+    /// kept in the database across restarts.
+    pub async fn state(&self, key: &str) -> sqlx::Result<Option<String>> {
+        database::get_state(&self.database, key).await
+    }
+
+    /// Stores `value` under `key`, or removes `key` if it is `None`.
+    pub async fn set_state(&self, key: &str, value: Option<&str>) -> sqlx::Result<()> {
+        match value {
+            Some(value) => database::set_state(&self.database, key, value).await,
+            None => database::remove_state(&self.database, key).await,
+        }
+    }
+
     #[doc(hidden)]
     pub async fn override_application_id(&self, new: Id<ApplicationMarker>) {
         *self.application_id.write().await = new;
+
+        let value = new.to_string();
+        if let Err(error) = database::set_state(&self.database, APPLICATION_ID_KEY, &value).await {
+            tracing::warn!(?error, "Failed to store application ID in the database");
+        }
     }
 }
 
@@ -163,24 +321,60 @@ impl std::fmt::Debug for Kernel {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("App")
             .field("application_id", &self.application_id)
-            .field("config", &self.config)
+            .field("config", &self.config())
             .field("is_shutdown", &self.is_shutdown())
             .finish()
     }
 }
 
 ///////////////////////////////////////////////////////////////////////
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Why the bot is shutting down, see [`Kernel::shutdown`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ShutdownReason {
+    /// Requested from the admin API, with the reason given by the
+    /// caller if any.
+    Admin(Option<String>),
     ApiServerFailed,
+    /// New configuration could only be applied partially.
+    ConfigReloadFailed,
+    /// One of the bot's services has panicked.
+    Panic,
     ShardFatalError(ShardId),
     Signal,
 }
 
+impl ShutdownReason {
+    /// Exit code of the process after shutting down for this reason.
+    ///
+    /// Requested shutdowns exit with `0` so supervisors can tell
+    /// them apart from crashes.
+    #[must_use]
+    pub const fn exit_code(&self) -> u8 {
+        match self {
+            Self::Admin(..) | Self::Signal => 0,
+            Self::ApiServerFailed => 10,
+            Self::Panic => 11,
+            Self::ShardFatalError(..) => 12,
+            Self::ConfigReloadFailed => 13,
+        }
+    }
+
+    #[must_use]
+    pub const fn is_failure(&self) -> bool {
+        self.exit_code() != 0
+    }
+}
+
 impl Display for ShutdownReason {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            Self::Admin(Some(reason)) => {
+                write!(f, "Shutdown requested from the admin API: {reason}")
+            }
+            Self::Admin(None) => f.write_str("Shutdown requested from the admin API"),
             Self::ApiServerFailed => f.write_str("API server failed"),
+            Self::ConfigReloadFailed => f.write_str("Configuration reload failed"),
+            Self::Panic => f.write_str("A service has panicked"),
             Self::ShardFatalError(id) => write!(f, "Shard {id} got a fatal error"),
             Self::Signal => f.write_str("Received shutdown signal"),
         }
@@ -208,7 +402,7 @@ impl Environment {
         }
     }
 
-    #[must_use]
+    #[must_use = "the environment could not be read if this returns an error"]
     pub fn from_env() -> Result<Self, memobot_env_vars::ReadVarError> {
         memobot_env_vars::var_parsed::<Environment, _>("MEMOBOT_ENV")
             .map(|v| v.unwrap_or(Environment::from_build()))
@@ -246,3 +440,121 @@ impl std::str::FromStr for Environment {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use claims::{assert_err, assert_ok_eq};
+    use std::sync::Once;
+
+    #[derive(Debug, Display)]
+    #[display(fmt = "Test task failed")]
+    struct TestError;
+    impl error_stack::Context for TestError {}
+
+    /// Kernel with an in-memory database that never talks to Discord.
+    async fn kernel() -> Kernel {
+        static ENV: Once = Once::new();
+        ENV.call_once(|| {
+            std::env::set_var("DISCORD_TOKEN", "test");
+            std::env::set_var("MEMOBOT_APPLICATION_ID", "1");
+            std::env::set_var("MEMOBOT_DATABASE_URL", "sqlite::memory:");
+
+            // Blocking tasks run on other threads, so the subscriber
+            // has to be the global one for their spans to be seen.
+            tracing::subscriber::set_global_default(tracing_subscriber::registry())
+                .expect("global subscriber is already set");
+        });
+
+        let config = Config::from_env().expect("invalid test config");
+        Kernel::init(config).await.expect("failed to init kernel")
+    }
+
+    /// Hub with a client so it can be told apart from the
+    /// hub that tasks would run in otherwise.
+    fn hub() -> Arc<sentry::Hub> {
+        let client = Arc::new(sentry::Client::from(sentry::ClientOptions::default()));
+        Arc::new(sentry::Hub::new(Some(client), Arc::default()))
+    }
+
+    /// Span and Sentry client of whatever is running this.
+    fn current_context() -> (Option<tracing::Id>, Option<Arc<sentry::Client>>) {
+        (
+            tracing::Span::current().id(),
+            sentry::Hub::current().client(),
+        )
+    }
+
+    fn is_same_context(
+        (span_id, client): &(Option<tracing::Id>, Option<Arc<sentry::Client>>),
+        span: &tracing::Span,
+        hub: &sentry::Hub,
+    ) -> bool {
+        let same_client = match (client, hub.client()) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, &b),
+            _ => false,
+        };
+        span_id.is_some() && *span_id == span.id() && same_client
+    }
+
+    #[tokio::test]
+    async fn spawn_keeps_span_and_hub() {
+        let kernel = kernel().await;
+        let span = tracing::info_span!("caller");
+        let hub = hub();
+
+        let handle = span.in_scope(|| {
+            sentry::Hub::run(hub.clone(), || kernel.spawn(async { current_context() }))
+        });
+
+        let context = handle.await.unwrap();
+        assert!(is_same_context(&context, &span, &hub));
+    }
+
+    #[tokio::test]
+    async fn spawn_blocking_result_keeps_span_and_hub() {
+        let kernel = kernel().await;
+        let span = tracing::info_span!("caller");
+        let hub = hub();
+
+        let task = span.in_scope(|| {
+            sentry::Hub::run(hub.clone(), || {
+                kernel.spawn_blocking_result(|| Ok::<_, Report<TestError>>(current_context()))
+            })
+        });
+
+        let context = task.await.unwrap();
+        assert!(is_same_context(&context, &span, &hub));
+    }
+
+    #[tokio::test]
+    async fn spawn_blocking_result_flattens_errors() {
+        let kernel = kernel().await;
+
+        assert_ok_eq!(
+            kernel
+                .spawn_blocking_result(|| Ok::<_, Report<TestError>>(1))
+                .await,
+            1
+        );
+
+        let error = assert_err!(
+            kernel
+                .spawn_blocking_result(|| Err::<(), _>(Report::new(TestError)))
+                .await
+        );
+        assert!(error.contains::<TestError>());
+    }
+
+    #[tokio::test]
+    async fn spawn_blocking_result_flattens_panics() {
+        let kernel = kernel().await;
+
+        let error = assert_err!(
+            kernel
+                .spawn_blocking_result(|| -> Result<(), TestError> { panic!("test panic") })
+                .await
+        );
+        assert!(error.contains::<tokio::task::JoinError>());
+    }
+}
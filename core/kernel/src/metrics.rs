@@ -0,0 +1,222 @@
+use prometheus::{Encoder, GaugeVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry};
+use twilight_http_ratelimiting::request::Path;
+use twilight_http_ratelimiting::{
+    GetBucketFuture, GetTicketFuture, HasBucketFuture, InMemoryRatelimiter, IsGloballyLockedFuture,
+    Ratelimiter,
+};
+use twilight_model::gateway::event::EventType;
+use twilight_model::gateway::ShardId;
+
+/// Prometheus metrics of the bot.
+///
+/// Metric names are part of the bot's public interface and
+/// should not be renamed once they are released.
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    registry: Registry,
+    background_tasks: IntGauge,
+    gateway_events: IntCounterVec,
+    http_ratelimited: IntCounter,
+    http_requests: IntCounter,
+    shard_latency: GaugeVec,
+    shard_reconnects: IntCounterVec,
+    shard_restarts: IntCounterVec,
+}
+
+impl Metrics {
+    #[must_use]
+    pub fn new() -> Self {
+        let registry = Registry::new();
+
+        let background_tasks = IntGauge::new(
+            "memobot_background_tasks",
+            "Number of background tasks currently running",
+        )
+        .expect("invalid metric");
+
+        let gateway_events = IntCounterVec::new(
+            Opts::new(
+                "memobot_gateway_events_total",
+                "Gateway events received by shards",
+            ),
+            &["shard", "kind"],
+        )
+        .expect("invalid metric");
+
+        let http_ratelimited = IntCounter::new(
+            "memobot_discord_http_ratelimited_total",
+            "Discord HTTP requests that had to wait for a ratelimit",
+        )
+        .expect("invalid metric");
+
+        let http_requests = IntCounter::new(
+            "memobot_discord_http_requests_total",
+            "Discord HTTP requests sent",
+        )
+        .expect("invalid metric");
+
+        let shard_latency = GaugeVec::new(
+            Opts::new(
+                "memobot_shard_heartbeat_latency_seconds",
+                "Average heartbeat latency of a shard",
+            ),
+            &["shard"],
+        )
+        .expect("invalid metric");
+
+        let shard_reconnects = IntCounterVec::new(
+            Opts::new(
+                "memobot_shard_reconnects_total",
+                "Times a shard reconnected to the gateway",
+            ),
+            &["shard"],
+        )
+        .expect("invalid metric");
+
+        let shard_restarts = IntCounterVec::new(
+            Opts::new(
+                "memobot_shard_restarts_total",
+                "Times a shard was restarted by its supervisor",
+            ),
+            &["shard"],
+        )
+        .expect("invalid metric");
+
+        for collector in [
+            Box::new(background_tasks.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(gateway_events.clone()),
+            Box::new(http_ratelimited.clone()),
+            Box::new(http_requests.clone()),
+            Box::new(shard_latency.clone()),
+            Box::new(shard_reconnects.clone()),
+            Box::new(shard_restarts.clone()),
+        ] {
+            registry
+                .register(collector)
+                .expect("metric is registered twice");
+        }
+
+        Self {
+            registry,
+            background_tasks,
+            gateway_events,
+            http_ratelimited,
+            http_requests,
+            shard_latency,
+            shard_reconnects,
+            shard_restarts,
+        }
+    }
+
+    /// Registry that extensions can register their own metrics in.
+    #[must_use]
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// Encodes all metrics in the Prometheus text format.
+    #[must_use]
+    pub fn encode(&self, background_tasks: usize) -> String {
+        self.background_tasks
+            .set(i64::try_from(background_tasks).unwrap_or(i64::MAX));
+
+        let mut buffer = Vec::new();
+        let encoder = prometheus::TextEncoder::new();
+        if let Err(error) = encoder.encode(&self.registry.gather(), &mut buffer) {
+            tracing::warn!(?error, "Failed to encode metrics");
+        }
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+impl Metrics {
+    pub fn record_gateway_event(&self, shard_id: ShardId, kind: EventType) {
+        let shard = shard_id.number().to_string();
+        let kind = match kind.name() {
+            Some(name) => name.to_string(),
+            None => format!("{kind:?}"),
+        };
+        self.gateway_events
+            .with_label_values(&[&shard, &kind])
+            .inc();
+    }
+
+    pub fn record_shard_latency(&self, shard_id: ShardId, latency: std::time::Duration) {
+        self.shard_latency
+            .with_label_values(&[&shard_id.number().to_string()])
+            .set(latency.as_secs_f64());
+    }
+
+    pub fn record_shard_reconnect(&self, shard_id: ShardId) {
+        self.shard_reconnects
+            .with_label_values(&[&shard_id.number().to_string()])
+            .inc();
+    }
+
+    pub fn record_shard_restart(&self, shard_id: ShardId) {
+        self.shard_restarts
+            .with_label_values(&[&shard_id.number().to_string()])
+            .inc();
+    }
+
+    /// Ratelimiter for the Discord HTTP client that counts
+    /// requests and ratelimits.
+    pub(crate) fn ratelimiter(&self) -> MeteredRatelimiter {
+        MeteredRatelimiter {
+            inner: InMemoryRatelimiter::new(),
+            ratelimited: self.http_ratelimited.clone(),
+            requests: self.http_requests.clone(),
+        }
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct MeteredRatelimiter {
+    inner: InMemoryRatelimiter,
+    ratelimited: IntCounter,
+    requests: IntCounter,
+}
+
+impl Ratelimiter for MeteredRatelimiter {
+    fn bucket(&self, path: &Path) -> GetBucketFuture {
+        self.inner.bucket(path)
+    }
+
+    fn is_globally_locked(&self) -> IsGloballyLockedFuture {
+        self.inner.is_globally_locked()
+    }
+
+    fn has(&self, path: &Path) -> HasBucketFuture {
+        self.inner.has(path)
+    }
+
+    fn ticket(&self, path: Path) -> GetTicketFuture {
+        self.requests.inc();
+
+        // Checked before queueing the request, so the bucket
+        // reflects the state the request has to wait for.
+        let bucket = self.inner.bucket(&path);
+        let globally_locked = self.inner.is_globally_locked();
+        let ticket = self.inner.ticket(path);
+        let ratelimited = self.ratelimited.clone();
+
+        Box::pin(async move {
+            let exhausted = bucket
+                .await
+                .ok()
+                .flatten()
+                .is_some_and(|v| v.remaining() == 0 && v.time_remaining().is_some());
+
+            if exhausted || globally_locked.await.unwrap_or(false) {
+                ratelimited.inc();
+            }
+            ticket.await
+        })
+    }
+}
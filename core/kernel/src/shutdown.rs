@@ -1,8 +1,49 @@
+use derive_more::Display;
+use futures::future::{join_all, BoxFuture};
+use futures::Future;
+use std::time::Duration;
 use tokio_util::sync::WaitForCancellationFuture;
 
 use super::Kernel;
 use crate::ShutdownReason;
 
+/// How long Sentry is given to send pending events.
+const SENTRY_FLUSH_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub(crate) type ShutdownHook = Box<dyn FnOnce(Kernel) -> BoxFuture<'static, ()> + Send>;
+
+/// Steps of a graceful shutdown, run in this order by
+/// [`Kernel::run_shutdown_phases`].
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ShutdownPhase {
+    /// The API server stops accepting requests.
+    #[display(fmt = "stop accepting HTTP requests")]
+    StopHttp,
+    /// Shards stop receiving events and wait for the
+    /// interactions they are handling.
+    #[display(fmt = "drain interactions")]
+    DrainInteractions,
+    /// Background tasks spawned with [`Kernel::spawn`] are waited for.
+    #[display(fmt = "flush background tasks")]
+    FlushBackgroundTasks,
+    /// Shards disconnect from the gateway and save their sessions.
+    #[display(fmt = "close shards")]
+    CloseShards,
+    /// Pending Sentry events are sent.
+    #[display(fmt = "flush Sentry")]
+    FlushSentry,
+}
+
+impl ShutdownPhase {
+    pub const ALL: [ShutdownPhase; 5] = [
+        ShutdownPhase::StopHttp,
+        ShutdownPhase::DrainInteractions,
+        ShutdownPhase::FlushBackgroundTasks,
+        ShutdownPhase::CloseShards,
+        ShutdownPhase::FlushSentry,
+    ];
+}
+
 impl Kernel {
     #[must_use]
     pub fn is_shutdown(&self) -> bool {
@@ -13,12 +54,97 @@ impl Kernel {
         self.shutdown.cancelled()
     }
 
+    /// Why the bot is shutting down, if it is.
+    #[must_use]
+    pub fn shutdown_reason(&self) -> Option<ShutdownReason> {
+        self.shutdown_reason.get().cloned()
+    }
+
+    /// Starts shutting down the bot. Only the reason of the first
+    /// call is kept, later calls are ignored.
     pub fn shutdown(&self, reason: ShutdownReason) {
-        if self.is_shutdown() {
+        if self.shutdown_reason.set(reason).is_err() {
             return;
         }
+        let reason = self
+            .shutdown_reason
+            .get()
+            .expect("shutdown reason should be set");
+
+        let level = if reason.is_failure() {
+            tracing::error!("{reason}; performing graceful shutdown...");
+            sentry::Level::Error
+        } else {
+            tracing::warn!("{reason}; performing graceful shutdown...");
+            sentry::Level::Info
+        };
+
+        sentry::add_breadcrumb(sentry::Breadcrumb {
+            category: Some("shutdown".into()),
+            message: Some(reason.to_string()),
+            level,
+            ..Default::default()
+        });
 
-        tracing::warn!("{reason}; performing graceful shutdown...");
         self.shutdown.cancel();
     }
+
+    /// Registers a hook that runs during `phase` of the shutdown.
+    ///
+    /// Hooks of the same phase run concurrently and the next phase
+    /// starts once all of them are finished. Hooks registered after
+    /// the phases have started are never run.
+    pub fn on_shutdown<F, Fut>(&self, phase: ShutdownPhase, hook: F)
+    where
+        F: FnOnce(Kernel) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let hook: ShutdownHook = Box::new(move |kernel| Box::pin(hook(kernel)));
+        self.shutdown_hooks
+            .lock()
+            .expect("shutdown hooks lock is poisoned")
+            .push((phase, hook));
+    }
+
+    /// Runs every [`ShutdownPhase`] in order along with their hooks.
+    pub async fn run_shutdown_phases(&self) {
+        let mut hooks = std::mem::take(
+            &mut *self
+                .shutdown_hooks
+                .lock()
+                .expect("shutdown hooks lock is poisoned"),
+        );
+
+        for phase in ShutdownPhase::ALL {
+            tracing::info!("Shutdown phase: {phase}");
+
+            let (current, rest): (Vec<_>, Vec<_>) =
+                hooks.into_iter().partition(|(v, _)| *v == phase);
+
+            hooks = rest;
+            join_all(current.into_iter().map(|(_, hook)| hook(self.clone()))).await;
+
+            match phase {
+                ShutdownPhase::FlushBackgroundTasks => {
+                    self.close_background_tasks_and_wait().await.await;
+                }
+                ShutdownPhase::FlushSentry => flush_sentry().await,
+                _ => {}
+            }
+        }
+    }
+}
+
+async fn flush_sentry() {
+    let Some(client) = sentry::Hub::current().client() else {
+        return;
+    };
+
+    let flushed = tokio::task::spawn_blocking(move || client.flush(Some(SENTRY_FLUSH_TIMEOUT)))
+        .await
+        .unwrap_or(false);
+
+    if !flushed {
+        tracing::warn!("Some Sentry events could not be sent in time");
+    }
 }
@@ -0,0 +1,82 @@
+use std::collections::BTreeMap;
+use std::panic::Location;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Background task spawned with [`Kernel::spawn`] or
+/// [`Kernel::spawn_blocking`] that has not finished yet.
+///
+/// [`Kernel::spawn`]: crate::Kernel::spawn
+/// [`Kernel::spawn_blocking`]: crate::Kernel::spawn_blocking
+#[derive(Debug, Clone)]
+pub struct BackgroundTask {
+    pub id: u64,
+    pub blocking: bool,
+    /// Where the task was spawned from.
+    pub location: &'static Location<'static>,
+    started_at: Instant,
+}
+
+impl BackgroundTask {
+    #[must_use]
+    pub fn elapsed(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct TaskList {
+    next_id: AtomicU64,
+    running: Mutex<BTreeMap<u64, BackgroundTask>>,
+}
+
+impl TaskList {
+    pub fn track(
+        self: &Arc<Self>,
+        location: &'static Location<'static>,
+        blocking: bool,
+    ) -> TaskGuard {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let task = BackgroundTask {
+            id,
+            blocking,
+            location,
+            started_at: Instant::now(),
+        };
+
+        self.running
+            .lock()
+            .expect("task list lock is poisoned")
+            .insert(id, task);
+
+        TaskGuard {
+            id,
+            list: self.clone(),
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<BackgroundTask> {
+        self.running
+            .lock()
+            .expect("task list lock is poisoned")
+            .values()
+            .cloned()
+            .collect()
+    }
+}
+
+/// Removes the task from its [`TaskList`] once the task is
+/// finished or dropped.
+pub(crate) struct TaskGuard {
+    id: u64,
+    list: Arc<TaskList>,
+}
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        if let Ok(mut running) = self.list.running.lock() {
+            running.remove(&self.id);
+        }
+    }
+}
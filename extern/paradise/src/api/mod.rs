@@ -1,6 +1,5 @@
 // TODO: clean messy actix code
-use actix_web::{http::header, web, HttpResponse};
-use constant_time_eq::constant_time_eq;
+use actix_web::{web, HttpResponse};
 use futures::future::BoxFuture;
 
 pub mod sanctuary;
@@ -46,9 +45,7 @@ impl actix_web::FromRequest for ApiAuthorization {
         req: &actix_web::HttpRequest,
         _payload: &mut actix_web::dev::Payload,
     ) -> Self::Future {
-        let service = req.app_data::<web::Data<Option<crate::Service>>>();
-        let service = service.and_then(|v| v.as_ref().as_ref().clone());
-        let Some(service) = service else {
+        let Some(service) = req.app_data::<web::Data<crate::Service>>() else {
             tracing::warn!(
                 "user tried to access resource with Paradise server configuration is disabled"
             );
@@ -57,22 +54,15 @@ impl actix_web::FromRequest for ApiAuthorization {
             ));
         };
 
-        let token = req
-            .headers()
-            .get(header::AUTHORIZATION)
-            .and_then(|v| v.to_str().ok())
-            .and_then(|v| v.strip_prefix("Bearer "))
-            .map(|v| v.to_string())
-            .unwrap_or_default();
-
-        // Performing "timing-safe equal"
-        let actual_token = service.config().token().as_bytes();
-        if !constant_time_eq(actual_token, token.as_bytes()) {
+        let config = service.config();
+        if !memobot_kernel::auth::has_bearer_token(req, config.token()) {
             tracing::warn!("user tried to access resource with invalid token");
             return Box::pin(futures::future::err(ApiAuthorizationError::InvalidToken));
         }
 
-        Box::pin(futures::future::ok(ApiAuthorization(service.clone())))
+        Box::pin(futures::future::ok(ApiAuthorization(
+            service.as_ref().clone(),
+        )))
     }
 }
 
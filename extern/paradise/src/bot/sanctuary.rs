@@ -15,19 +15,17 @@ impl error_stack::Context for AlertEveryoneError {}
 pub async fn alert_everyone(service: &Service, is_online: bool) -> Result<(), AlertEveryoneError> {
     tracing::info!(?is_online, "Sending alert message to Paradise");
 
-    let message;
     let config = service.config();
-
-    if is_online {
-        message = format!(
+    let message = if is_online {
+        format!(
             "🎉  **Sanctuary is back online!** 🎉\nJoin us at: `{}:{}`\n\n{}",
             config.sanctuary_addr(),
             config.sanctuary_port(),
             config.alert_role_id().mention(),
-        );
+        )
     } else {
-        message = format!("❌  **Sanctuary is offline** ❌\nJoin with us next time.");
-    }
+        "❌  **Sanctuary is offline** ❌\nJoin with us next time.".to_string()
+    };
 
     let message = service
         .kernel()
@@ -48,5 +46,8 @@ pub async fn alert_everyone(service: &Service, is_online: bool) -> Result<(), Al
         })
         .await?;
 
+    let status = if is_online { "online" } else { "offline" };
+    service.alerts_sent().with_label_values(&[status]).inc();
+
     Ok(())
 }
@@ -1,28 +1,59 @@
+use actix_web::web;
+use error_stack::{Result, ResultExt};
+use memobot_kernel::extension::{Extension, ExtensionLoadError, PendingReload};
 use memobot_kernel::Kernel;
-use std::sync::Arc;
+use prometheus::{IntCounterVec, Opts};
+use std::sync::{Arc, RwLock};
 
 use crate::config::Config;
 
 #[derive(Debug, Clone)]
 pub struct Service {
-    config: Arc<Config>,
+    alerts_sent: IntCounterVec,
+    config: Arc<RwLock<Arc<Config>>>,
     kernel: Kernel,
 }
 
 impl Service {
     #[must_use]
     pub fn new(config: Config, kernel: Kernel) -> Self {
+        let alerts_sent = IntCounterVec::new(
+            Opts::new(
+                "memobot_paradise_alerts_sent_total",
+                "Sanctuary alerts sent to Paradise",
+            ),
+            &["status"],
+        )
+        .expect("invalid metric");
+
+        if let Err(error) = kernel
+            .metrics()
+            .registry()
+            .register(Box::new(alerts_sent.clone()))
+        {
+            tracing::warn!(?error, "Failed to register Paradise metrics");
+        }
+
         Self {
-            config: Arc::new(config),
+            alerts_sent,
+            config: Arc::new(RwLock::new(Arc::new(config))),
             kernel,
         }
     }
 }
 
 impl Service {
+    /// Counter of alerts sent, labeled with the sanctuary status.
+    #[must_use]
+    pub fn alerts_sent(&self) -> &IntCounterVec {
+        &self.alerts_sent
+    }
+
+    /// Snapshot of the current configuration, it may be
+    /// replaced when the configuration is reloaded.
     #[must_use]
-    pub fn config(&self) -> &Config {
-        &self.config
+    pub fn config(&self) -> Arc<Config> {
+        self.config.read().expect("config lock is poisoned").clone()
     }
 
     #[must_use]
@@ -30,3 +61,42 @@ impl Service {
         &self.kernel
     }
 }
+
+impl Extension for Service {
+    fn name(&self) -> &'static str {
+        Self::static_name()
+    }
+
+    fn static_name() -> &'static str {
+        "paradise"
+    }
+
+    fn load(kernel: &Kernel) -> Result<Option<Self>, ExtensionLoadError> {
+        let config = Config::from_env()
+            .change_context(ExtensionLoadError)
+            .attach_printable("failed to load Paradise configuration")?;
+
+        Ok(config.map(|v| Self::new(v, kernel.clone())))
+    }
+
+    fn configure_api(&self, cfg: &mut web::ServiceConfig) {
+        cfg.app_data(web::Data::new(self.clone()));
+        crate::api::configure(cfg);
+    }
+
+    fn reload(&self, _kernel: &Kernel) -> Result<PendingReload, ExtensionLoadError> {
+        let config = Config::from_env()
+            .change_context(ExtensionLoadError)
+            .attach_printable("failed to reload Paradise configuration")?;
+
+        // Disabling Paradise needs its routes to be unmounted
+        let Some(config) = config else {
+            return Ok(PendingReload::default().requires_restart(["guild_id"]));
+        };
+
+        let current = self.config.clone();
+        Ok(PendingReload::new(move || {
+            *current.write().expect("config lock is poisoned") = Arc::new(config);
+        }))
+    }
+}